@@ -58,6 +58,24 @@ where
     }
 }
 
+impl Vec2<f32> {
+    /// Returns true if every component differs from `other` by at most `eps`.
+    #[allow(dead_code)]
+    pub fn approx_eq(&self, other: &Vec2<f32>, eps: f32) -> bool {
+        (self.x - other.x).abs() <= eps && (self.y - other.y).abs() <= eps
+    }
+
+    /// Linear interpolation from `self` (t = 0) to `other` (t = 1).
+    pub fn lerp(self, other: Vec2<f32>, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    #[allow(dead_code)]
+    pub fn lerp_clamped(self, other: Vec2<f32>, t: f32) -> Self {
+        self.lerp(other, t.clamp(0.0, 1.0))
+    }
+}
+
 impl<T> fmt::Display for Vec2<T>
 where
     T: fmt::Display,
@@ -129,6 +147,24 @@ where
 }
 
 impl Vec3<f32> {
+    /// Returns true if every component differs from `other` by at most `eps`.
+    #[allow(dead_code)]
+    pub fn approx_eq(&self, other: &Vec3<f32>, eps: f32) -> bool {
+        (self.x - other.x).abs() <= eps
+            && (self.y - other.y).abs() <= eps
+            && (self.z - other.z).abs() <= eps
+    }
+
+    /// Linear interpolation from `self` (t = 0) to `other` (t = 1).
+    pub fn lerp(self, other: Vec3<f32>, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    #[allow(dead_code)]
+    pub fn lerp_clamped(self, other: Vec3<f32>, t: f32) -> Self {
+        self.lerp(other, t.clamp(0.0, 1.0))
+    }
+
     pub fn norm(&self) -> f32 {
         (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
@@ -136,14 +172,14 @@ impl Vec3<f32> {
     pub fn normalize(&self, l: f32) -> Self {
         let length = self.norm();
         if length == 0.0 {
-            return self.clone();
+            return *self;
         }
         let factor = l / length;
         Vec3f::new(self.x * factor, self.y * factor, self.z * factor)
     }
 
     #[allow(dead_code)]
-    pub fn to_i(&self) -> Vec3<i32> {
+    pub fn to_i(self) -> Vec3<i32> {
         Vec3 {
             x: self.x as i32,
             y: self.y as i32,
@@ -151,7 +187,7 @@ impl Vec3<f32> {
         }
     }
 
-    pub fn to_mat(&self) -> Matrix {
+    pub fn to_mat(self) -> Matrix {
         let mut m = Matrix::new(4, 1);
         m[0][0] = self.x;
         m[1][0] = self.y;
@@ -162,7 +198,8 @@ impl Vec3<f32> {
 }
 
 impl Vec3<i32> {
-    pub fn to_f(&self) -> Vec3<f32> {
+    #[allow(dead_code)]
+    pub fn to_f(self) -> Vec3<f32> {
         Vec3 {
             x: self.x as f32,
             y: self.y as f32,
@@ -195,6 +232,26 @@ where
     }
 }
 
+#[allow(dead_code)]
+impl Vec3<f32> {
+    /// Mirrors `self` about the plane with the given unit `normal`.
+    pub fn reflect(&self, normal: Vec3f) -> Vec3f {
+        *self - normal * 2.0 * self.dot(normal)
+    }
+
+    /// Bends the unit incident direction `self` through a surface with unit `normal` by Snell's
+    /// law, where `eta` is the ratio of refractive indices (outside / inside). Returns `None` on
+    /// total internal reflection.
+    pub fn refract(&self, normal: Vec3f, eta: f32) -> Option<Vec3f> {
+        let cos_i = -self.dot(normal);
+        let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+        if k < 0.0 {
+            return None;
+        }
+        Some(*self * eta + normal * (eta * cos_i - k.sqrt()))
+    }
+}
+
 impl<T> fmt::Display for Vec3<T>
 where
     T: fmt::Display,
@@ -204,13 +261,80 @@ where
     }
 }
 
+/// Homogeneous vector, transformed by a 4x4 `Matrix` as a column (`m * v`) or a row (`v * m`).
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vec4<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+    pub w: T,
+}
+
+#[allow(dead_code)]
+impl<T> Vec4<T>
+where
+    T: Copy + Default,
+{
+    pub fn new(x: T, y: T, z: T, w: T) -> Self {
+        Vec4 { x, y, z, w }
+    }
+}
+
+#[allow(dead_code)]
+impl Vec4<f32> {
+    /// Point `(x, y, z)` in homogeneous coordinates, with w = 1.
+    pub fn from_point(p: Vec3f) -> Self {
+        Vec4::new(p.x, p.y, p.z, 1.0)
+    }
+
+    /// Divides by w, projecting back to three dimensions.
+    pub fn to_point(self) -> Vec3f {
+        Vec3f::new(self.x / self.w, self.y / self.w, self.z / self.w)
+    }
+
+    fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+
+    fn from_array([x, y, z, w]: [f32; 4]) -> Self {
+        Vec4 { x, y, z, w }
+    }
+}
+
+impl<T> fmt::Display for Vec4<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {}, {}, {})", self.x, self.y, self.z, self.w)
+    }
+}
+
 #[allow(dead_code)]
 pub type Vec2f = Vec2<f32>;
 pub type Vec2i = Vec2<i32>;
 pub type Vec3f = Vec3<f32>;
+#[allow(dead_code)]
 pub type Vec3i = Vec3<i32>;
+#[allow(dead_code)]
+pub type Vec4f = Vec4<f32>;
 
-#[derive(Debug, Clone)]
+/// Barycentric weights `(u, v, w)` of `p` relative to the triangle `abc`, so that
+/// `p = a * u + b * v + c * w` and the weights sum to 1. A weight is negative when `p` lies
+/// outside the triangle. Degenerate triangles have no weights and yield `(-1, 1, 1)`.
+#[allow(dead_code)]
+pub fn barycentric(a: Vec2f, b: Vec2f, c: Vec2f, p: Vec2f) -> Vec3f {
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if area.abs() < f32::EPSILON {
+        return Vec3f::new(-1.0, 1.0, 1.0);
+    }
+    let v = ((p.x - a.x) * (c.y - a.y) - (c.x - a.x) * (p.y - a.y)) / area;
+    let w = ((b.x - a.x) * (p.y - a.y) - (p.x - a.x) * (b.y - a.y)) / area;
+    Vec3f::new(1.0 - v - w, v, w)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Matrix {
     m: Vec<Vec<f32>>,
     rows: usize,
@@ -227,6 +351,19 @@ impl Matrix {
         }
     }
 
+    /// Returns true if both matrices have the same shape and every element differs by at most
+    /// `eps`.
+    pub fn approx_eq(&self, other: &Matrix, eps: f32) -> bool {
+        self.rows == other.rows
+            && self.cols == other.cols
+            && self
+                .m
+                .iter()
+                .flatten()
+                .zip(other.m.iter().flatten())
+                .all(|(a, b)| (a - b).abs() <= eps)
+    }
+
     pub fn identity(dimensions: usize) -> Self {
         let mut e = Self::new(dimensions, dimensions);
         for i in 0..e.rows {
@@ -239,14 +376,67 @@ impl Matrix {
         e
     }
 
+    /// Multiplies every element by `s`.
+    pub fn scale_scalar(&self, s: f32) -> Matrix {
+        Matrix {
+            m: self
+                .m
+                .iter()
+                .map(|row| row.iter().map(|v| v * s).collect())
+                .collect(),
+            rows: self.rows,
+            cols: self.cols,
+        }
+    }
+
+    /// Minimal perspective divide for a camera at distance `z` on the +z axis: only w depends on
+    /// z, so depth is not remapped to a near/far range. Kept for existing callers; prefer
+    /// `perspective`, which gives well-defined depth.
     pub fn projection(z: f32) -> Self {
         let mut mat = Matrix::identity(4);
         mat[3][2] = -1.0 / z;
         mat
     }
 
+    /// Perspective projection for a camera looking down -z. Points on the near plane map to
+    /// z = 1 and points on the far plane to z = -1, so nearer fragments have larger depth.
+    pub fn perspective(fov_y_radians: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fov_y_radians / 2.0).tan();
+        let mut mat = Matrix::new(4, 4);
+        mat[0][0] = f / aspect;
+        mat[1][1] = f;
+        mat[2][2] = (far + near) / (far - near);
+        mat[2][3] = 2.0 * far * near / (far - near);
+        mat[3][2] = -1.0;
+        mat
+    }
+
+    /// Orthographic projection of a box `2 * size` high (and `aspect` times as wide) looking
+    /// down -z. Uses the same depth convention as `perspective`.
+    pub fn orthographic(size: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let mut mat = Matrix::identity(4);
+        mat[0][0] = 1.0 / (size * aspect);
+        mat[1][1] = 1.0 / size;
+        mat[2][2] = 2.0 / (far - near);
+        mat[2][3] = (far + near) / (far - near);
+        mat
+    }
+
+    /// Viewport transform mapping normalized device coordinates onto the `w`x`h` pixel rectangle
+    /// at `(x, y)`, with z in -1..=1 mapped to `0..=depth`.
+    pub fn viewport(x: i32, y: i32, w: i32, h: i32, depth: f32) -> Self {
+        let mut mat = Matrix::identity(4);
+        mat[0][3] = x as f32 + w as f32 / 2.0;
+        mat[1][3] = y as f32 + h as f32 / 2.0;
+        mat[2][3] = depth / 2.0;
+        mat[0][0] = w as f32 / 2.0;
+        mat[1][1] = h as f32 / 2.0;
+        mat[2][2] = depth / 2.0;
+        mat
+    }
+
     pub fn from_vec(v: Vec<Vec<f32>>) -> Self {
-        assert!(v.len() >= 1);
+        assert!(!v.is_empty());
 
         let first_row_len = v[0].len();
         assert!(v.iter().all(|r| r.len() == first_row_len));
@@ -258,6 +448,36 @@ impl Matrix {
         }
     }
 
+    /// Builds a matrix from its rows, failing if the rows are empty or of unequal length.
+    pub fn from_rows(rows: Vec<Vec<f32>>) -> Result<Self, String> {
+        let Some(first) = rows.first() else {
+            return Err("Matrix must have at least one row".to_string());
+        };
+        let cols = first.len();
+        if let Some((i, row)) = rows.iter().enumerate().find(|(_, r)| r.len() != cols) {
+            return Err(format!(
+                "Row {} has {} columns, expected {}",
+                i,
+                row.len(),
+                cols
+            ));
+        }
+
+        Ok(Matrix {
+            rows: rows.len(),
+            cols,
+            m: rows,
+        })
+    }
+
+    pub fn from_array<const R: usize, const C: usize>(a: [[f32; C]; R]) -> Self {
+        Matrix {
+            m: a.iter().map(|row| row.to_vec()).collect(),
+            rows: R,
+            cols: C,
+        }
+    }
+
     pub fn nrows(&self) -> usize {
         self.rows
     }
@@ -347,6 +567,12 @@ impl Matrix {
             self[2][0] / self[3][0],
         )
     }
+
+    /// Transforms the point `p` by this 4x4 matrix, lifting it to homogeneous coordinates with
+    /// w = 1 and dividing by the resulting w.
+    pub fn transform_point(&self, p: Vec3f) -> Vec3f {
+        (self * Vec4f::from_point(p)).to_point()
+    }
 }
 
 impl fmt::Display for Matrix {
@@ -356,7 +582,7 @@ impl fmt::Display for Matrix {
             for j in 0..self.cols {
                 s.push_str(&format!("{:.2} ", self[i][j]));
                 if j < self.cols - 1 {
-                    s.push_str("\t");
+                    s.push('\t');
                 }
             }
             s.push('\n');
@@ -397,24 +623,166 @@ impl Mul for Matrix {
     }
 }
 
+impl Mul<Vec4f> for &Matrix {
+    type Output = Vec4f;
+
+    /// Treats `v` as a column vector. Panics unless the matrix has 4 columns.
+    fn mul(self, v: Vec4f) -> Vec4f {
+        assert!(
+            self.cols == 4,
+            "Cannot multiply a {}x{} matrix by a 4-vector",
+            self.rows,
+            self.cols
+        );
+        let v = v.to_array();
+        let mut res = [0.0; 4];
+        for (i, out) in res.iter_mut().enumerate().take(self.rows) {
+            *out = (0..4).map(|j| self[i][j] * v[j]).sum();
+        }
+        Vec4f::from_array(res)
+    }
+}
+
+impl Mul<Vec4f> for Matrix {
+    type Output = Vec4f;
+
+    fn mul(self, v: Vec4f) -> Vec4f {
+        &self * v
+    }
+}
+
+impl Mul<&Matrix> for Vec4f {
+    type Output = Vec4f;
+
+    /// Treats `self` as a row vector. Panics unless the matrix has 4 rows.
+    fn mul(self, m: &Matrix) -> Vec4f {
+        assert!(
+            m.rows == 4,
+            "Cannot multiply a 4-vector by a {}x{} matrix",
+            m.rows,
+            m.cols
+        );
+        let v = self.to_array();
+        let mut res = [0.0; 4];
+        for (j, out) in res.iter_mut().enumerate().take(m.cols) {
+            *out = (0..4).map(|i| v[i] * m[i][j]).sum();
+        }
+        Vec4f::from_array(res)
+    }
+}
+
+impl Mul<Matrix> for Vec4f {
+    type Output = Vec4f;
+
+    fn mul(self, m: Matrix) -> Vec4f {
+        self * &m
+    }
+}
+
+impl Add for Matrix {
+    type Output = Matrix;
+
+    /// Element-wise sum. Panics if the dimensions differ.
+    fn add(self, rhs: Self) -> Self::Output {
+        assert!(
+            self.rows == rhs.rows && self.cols == rhs.cols,
+            "Cannot add a {}x{} matrix to a {}x{} matrix",
+            rhs.rows,
+            rhs.cols,
+            self.rows,
+            self.cols
+        );
+        let mut res = self;
+        for i in 0..res.rows {
+            for j in 0..res.cols {
+                res[i][j] += rhs[i][j];
+            }
+        }
+
+        res
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn approx_eq(l: &Matrix, r: &Matrix) -> bool {
-        if l.rows != r.rows || l.cols != r.cols {
-            return false;
-        }
+        l.approx_eq(r, f32::EPSILON)
+    }
 
-        for i in 0..l.rows {
-            for j in 0..l.cols {
-                if (l[i][j] - r[i][j]).abs() > f32::EPSILON {
-                    return false;
-                }
-            }
-        }
+    #[test]
+    fn test_barycentric() {
+        let (a, b, c) = (
+            Vec2f::new(0.0, 0.0),
+            Vec2f::new(4.0, 1.0),
+            Vec2f::new(1.0, 5.0),
+        );
+        let third = 1.0 / 3.0;
+        let centroid = Vec2f::new(5.0 / 3.0, 2.0);
+        assert!(barycentric(a, b, c, centroid).approx_eq(&Vec3f::new(third, third, third), 1e-6));
+        assert!(barycentric(a, b, c, a).approx_eq(&Vec3f::new(1.0, 0.0, 0.0), 0.0));
+        assert!(barycentric(a, b, c, c).approx_eq(&Vec3f::new(0.0, 0.0, 1.0), 0.0));
+        // Clockwise winding gives the same weights
+        assert!(barycentric(a, c, b, b).approx_eq(&Vec3f::new(0.0, 0.0, 1.0), 0.0));
+
+        let outside = barycentric(a, b, c, Vec2f::new(-1.0, -1.0));
+        assert!(outside.x < 0.0 || outside.y < 0.0 || outside.z < 0.0);
+        assert!((outside.x + outside.y + outside.z - 1.0).abs() < 1e-6);
+
+        let degenerate = barycentric(a, b, Vec2f::new(8.0, 2.0), Vec2f::new(2.0, 0.5));
+        assert!(degenerate.x < 0.0 || degenerate.y < 0.0 || degenerate.z < 0.0);
+    }
 
-        true
+    #[test]
+    fn test_perspective() {
+        let (near, far) = (0.5, 20.0);
+        let m = Matrix::perspective(std::f32::consts::FRAC_PI_2, 2.0, near, far);
+        let at = |x, y, z| m.transform_point(Vec3f::new(x, y, z));
+        // The near plane maps to z = 1 and the far plane to z = -1
+        assert!((at(0.0, 0.0, -near).z - 1.0).abs() < 1e-5);
+        assert!((at(0.0, 0.0, -far).z + 1.0).abs() < 1e-5);
+        assert!(at(0.0, 0.0, -5.0).z.abs() < 1.0);
+        // A 90 degree field of view reaches y = +-1 at depth -d where |y| = d, and x is
+        // narrowed by the aspect ratio
+        assert!(at(0.0, 3.0, -3.0).approx_eq(&Vec3f::new(0.0, 1.0, at(0.0, 0.0, -3.0).z), 1e-5));
+        assert!((at(6.0, 0.0, -3.0).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_viewport() {
+        let m = Matrix::viewport(10, 20, 100, 50, 255.0);
+        let map = |x, y, z| m.transform_point(Vec3f::new(x, y, z));
+        assert!(map(-1.0, -1.0, -1.0).approx_eq(&Vec3f::new(10.0, 20.0, 0.0), 1e-4));
+        assert!(map(1.0, 1.0, 1.0).approx_eq(&Vec3f::new(110.0, 70.0, 255.0), 1e-4));
+        assert!(map(0.0, 0.0, 0.0).approx_eq(&Vec3f::new(60.0, 45.0, 127.5), 1e-4));
+    }
+
+    #[test]
+    fn test_mul_vec4() {
+        let m = Matrix::from_array([
+            [1.0, 2.0, 0.0, 1.0],
+            [0.0, 1.0, 3.0, 0.0],
+            [2.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 2.0],
+        ]);
+        let v = Vec4f::new(1.0, 2.0, 3.0, 1.0);
+        assert_eq!(&m * v, Vec4f::new(6.0, 11.0, 5.0, 2.0));
+        assert_eq!(v * &m, Vec4f::new(7.0, 4.0, 9.0, 3.0));
+        // Agrees with multiplying by a 4x1 matrix
+        let column = m.clone() * Vec3f::new(1.0, 2.0, 3.0).to_mat();
+        assert!(m
+            .transform_point(Vec3f::new(1.0, 2.0, 3.0))
+            .approx_eq(&column.to_vec(), 0.0));
+        assert!(m
+            .transform_point(Vec3f::new(1.0, 2.0, 3.0))
+            .approx_eq(&Vec3f::new(3.0, 5.5, 2.5), 1e-6));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mul_vec4_dimension_mismatch() {
+        let _ = Matrix::identity(3) * Vec4f::new(1.0, 2.0, 3.0, 1.0);
     }
 
     #[test]
@@ -468,6 +836,30 @@ mod tests {
         assert!(approx_eq(&(a * b), &c));
     }
 
+    #[test]
+    fn test_scale_scalar_and_add() {
+        let a = Matrix::from_array([[1.0, 2.0], [3.0, 4.0]]);
+        let b = Matrix::from_array([[5.0, 6.0], [7.0, 8.0]]);
+        assert!(approx_eq(
+            &a.scale_scalar(2.0),
+            &Matrix::from_array([[2.0, 4.0], [6.0, 8.0]])
+        ));
+        assert!(approx_eq(
+            &(a.clone() + b.clone()),
+            &Matrix::from_array([[6.0, 8.0], [10.0, 12.0]])
+        ));
+        assert!(approx_eq(
+            &(a.scale_scalar(0.5) + b.scale_scalar(0.5)),
+            &Matrix::from_array([[3.0, 4.0], [5.0, 6.0]])
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot add a 2x3 matrix to a 2x2 matrix")]
+    fn test_add_dimension_mismatch() {
+        let _ = Matrix::identity(2) + Matrix::new(2, 3);
+    }
+
     #[test]
     fn test_inverse() {
         let m = Matrix {
@@ -493,4 +885,83 @@ mod tests {
             None => false,
         });
     }
+
+    #[test]
+    fn test_vec_approx_eq() {
+        let a = Vec3f::new(1.0, 2.0, 3.0);
+        assert!(a.approx_eq(&Vec3f::new(1.0, 2.0, 3.0), 0.0));
+        assert!(a.approx_eq(&Vec3f::new(1.0005, 1.9995, 3.0), 1e-3));
+        assert!(!a.approx_eq(&Vec3f::new(1.0, 2.0, 3.01), 1e-3));
+
+        let b = Vec2f::new(1.0, 2.0);
+        assert!(b.approx_eq(&Vec2f::new(1.0, 2.0), 0.0));
+        assert!(b.approx_eq(&Vec2f::new(1.0005, 1.9995), 1e-3));
+        assert!(!b.approx_eq(&Vec2f::new(0.99, 2.0), 1e-3));
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let rows = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        let m = Matrix::from_rows(rows.clone()).unwrap();
+        assert_eq!((m.nrows(), m.ncols()), (2, 3));
+        assert_eq!(m.m, rows);
+        assert!(approx_eq(
+            &m,
+            &Matrix::from_array([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]])
+        ));
+
+        assert_eq!(
+            Matrix::from_rows(vec![vec![1.0, 2.0], vec![3.0]]).unwrap_err(),
+            "Row 1 has 1 columns, expected 2"
+        );
+        assert!(Matrix::from_rows(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = Vec3f::new(0.0, 2.0, -4.0);
+        let b = Vec3f::new(10.0, 4.0, 4.0);
+        assert!(a.lerp(b, 0.0).approx_eq(&a, 1e-6));
+        assert!(a.lerp(b, 1.0).approx_eq(&b, 1e-6));
+        assert!(a.lerp(b, 0.5).approx_eq(&Vec3f::new(5.0, 3.0, 0.0), 1e-6));
+        assert!(a.lerp(b, 2.0).approx_eq(&Vec3f::new(20.0, 6.0, 12.0), 1e-6));
+        assert!(a.lerp_clamped(b, 2.0).approx_eq(&b, 1e-6));
+        assert!(a.lerp_clamped(b, -1.0).approx_eq(&a, 1e-6));
+
+        let c = Vec2f::new(0.0, 1.0);
+        let d = Vec2f::new(1.0, 0.0);
+        assert!(c.lerp(d, 0.0).approx_eq(&c, 1e-6));
+        assert!(c.lerp(d, 1.0).approx_eq(&d, 1e-6));
+        assert!(c.lerp(d, 0.5).approx_eq(&Vec2f::new(0.5, 0.5), 1e-6));
+        assert!(c.lerp_clamped(d, 1.5).approx_eq(&d, 1e-6));
+        assert!(c.lerp_clamped(d, -0.5).approx_eq(&c, 1e-6));
+    }
+
+    #[test]
+    fn test_reflect_refract() {
+        let up = Vec3f::new(0.0, 1.0, 0.0);
+        assert!(Vec3f::new(1.0, -1.0, 0.0)
+            .reflect(up)
+            .approx_eq(&Vec3f::new(1.0, 1.0, 0.0), 1e-6));
+
+        let down = Vec3f::new(0.0, -1.0, 0.0);
+        assert!(down.refract(up, 1.5).unwrap().approx_eq(&down, 1e-6));
+
+        let grazing = Vec3f::new(1.0, -0.01, 0.0).normalize(1.0);
+        assert!(grazing.refract(up, 1.5).is_none());
+    }
+
+    #[test]
+    fn test_matrix_clone_eq() {
+        let original = Matrix::identity(3);
+        let mut copy = original.clone();
+        assert_eq!(copy, original);
+
+        copy[1][2] = 0.5;
+        assert_eq!(original, Matrix::identity(3));
+        assert_ne!(copy, original);
+        assert!(!copy.approx_eq(&original, 0.1));
+        assert!(copy.approx_eq(&original, 0.5));
+        assert!(!Matrix::identity(3).approx_eq(&Matrix::identity(4), 1.0));
+    }
 }
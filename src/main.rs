@@ -4,7 +4,7 @@ mod renderer;
 mod tgaimage;
 
 use model::Model;
-use renderer::Renderer;
+use renderer::{CameraMode, Renderer};
 
 fn main() {
     let width = 800;
@@ -16,8 +16,18 @@ fn main() {
             std::process::exit(1);
         }
     };
+    println!("Model loaded. {}", model.stats());
 
     let mut renderer = Renderer::new(width, height);
-    renderer.render_model_with_camera(&model).unwrap();
+    renderer
+        .render_model_with_camera(
+            &model,
+            CameraMode::Perspective {
+                fov: std::f32::consts::FRAC_PI_6,
+                near: 0.1,
+                far: 100.0,
+            },
+        )
+        .unwrap();
     renderer.save_tga_image("output.tga").unwrap();
 }
@@ -1,102 +1,482 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::SplitWhitespace;
 
 use crate::geometry::{Vec2f, Vec3f};
 
+/// Vertex order of a face as seen from the side its normal points to.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Stored in a face corner for a uv or normal index the OBJ file leaves out.
+pub const NO_INDEX: usize = usize::MAX;
+
+/// Lines per chunk handed to a worker thread by `Model::new_parallel`.
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK_LINES: usize = 4096;
+
+/// One line of an OBJ file, parsed independently of the lines around it.
+enum Statement {
+    Vertex(Vec3f),
+    Uv(Vec2f),
+    Normal(Vec3f),
+    /// Parameter space vertex from `vp`, as (u, v, w)
+    ParamVertex(Vec3f),
+    /// `[vertex, uv, normal]` indices of each corner
+    Face(Vec<[usize; 3]>),
+    /// Vertex indices of an `l` polyline
+    Line(Vec<usize>),
+    /// Start of an `o` or `g` group
+    Group(String),
+    /// Smoothing group set by `s`, 0 for `s off`
+    Smoothing(u32),
+    MaterialLibs(Vec<String>),
+    UseMaterial(Option<String>),
+    /// Blank lines, comments and unsupported statements
+    Ignored,
+}
+
+/// Surface properties from an MTL `newmtl` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// Diffuse texture (`map_Kd`), resolved relative to the MTL file
+    pub diffuse_map: Option<PathBuf>,
+    /// Diffuse color (`Kd`) as RGB, white if not given
+    pub diffuse_color: [f32; 3],
+    /// Specular color (`Ks`) as RGB, black if not given
+    pub specular_color: [f32; 3],
+}
+
+/// Summary of a model's contents, as returned by `Model::stats`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct ModelStats {
+    pub verts: usize,
+    pub uvs: usize,
+    pub normals: usize,
+    pub faces: usize,
+    /// Triangles the faces are fanned into for rasterization, one fewer than corners per face
+    pub triangles: usize,
+    /// Minimum and maximum corners of the vertices, `None` for a model without any
+    pub bounding_box: Option<(Vec3f, Vec3f)>,
+}
+
+impl fmt::Display for ModelStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "verts: {}, uvs: {}, normals: {}, faces: {}, triangles: {}",
+            self.verts, self.uvs, self.normals, self.faces, self.triangles
+        )
+    }
+}
+
 pub struct Model {
     verts: Vec<Vec3f>,
     #[allow(dead_code)]
     uvs: Vec<Vec2f>,
-    #[allow(dead_code)]
     normals: Vec<Vec3f>,
-    faces: Vec<Vec<Vec<usize>>>,
+    /// `[vertex, uv, normal]` indices of every face corner, face after face
+    corners: Vec<[usize; 3]>,
+    /// Range of `corners` each face spans
+    faces: Vec<Range<usize>>,
+    /// Vertex indices of `l` polylines
+    lines: Vec<Vec<usize>>,
+    /// Parameter space vertices from `vp` statements, as (u, v, w)
+    param_verts: Vec<Vec3f>,
+    groups: Vec<(String, Range<usize>)>,
+    /// Smoothing group of each face: `None` before any `s` statement, `Some(0)` for `s off`
+    smoothing_groups: Vec<Option<u32>>,
+    materials: Vec<Material>,
+    /// `mtllib` files that could not be read, resolved against the OBJ file
+    missing_material_libs: Vec<PathBuf>,
+    /// Material selected by the first `usemtl` statement
+    used_material: Option<String>,
+    /// Index into `materials` of the material each face uses
+    face_materials: Vec<Option<usize>>,
+    /// Normalized geometric face normals, computed on first use
+    face_normals: RefCell<Option<Vec<Vec3f>>>,
 }
 
 impl Model {
     pub fn new(filename: &str) -> Result<Self, String> {
-        let mut verts: Vec<Vec3f> = Vec::new();
-        let mut uvs: Vec<Vec2f> = Vec::new();
-        let mut normals: Vec<Vec3f> = Vec::new();
-        let mut faces: Vec<Vec<Vec<usize>>> = Vec::new();
-
-        let Ok(file) = File::open(&Path::new(filename)) else {
+        let Ok(file) = File::open(Path::new(filename)) else {
             return Err("Failed to open file".to_string());
         };
-        let reader = BufReader::new(file);
-
-        let parse_coordinate = |parts: &mut SplitWhitespace<'_>, message| {
-            parts
-                .next()
-                .ok_or("Missing coordinate")?
-                .parse::<f32>()
-                .map_err(|_| message)
+        let statements = BufReader::new(file)
+            .lines()
+            .map(|line| parse_statement(&line.map_err(|e| e.to_string())?));
+
+        Self::from_statements(filename, statements)
+    }
+
+    /// Loads like `new`, parsing chunks of lines on the rayon thread pool. Statements are then
+    /// applied in file order, so the model is identical to the one `new` returns.
+    #[cfg(feature = "rayon")]
+    #[allow(dead_code)]
+    pub fn new_parallel(filename: &str) -> Result<Self, String> {
+        use rayon::prelude::*;
+
+        let Ok(contents) = std::fs::read_to_string(filename) else {
+            return Err("Failed to open file".to_string());
         };
+        let lines: Vec<&str> = contents.lines().collect();
+        let statements: Vec<Result<Statement, String>> = lines
+            .par_chunks(PARALLEL_CHUNK_LINES)
+            .flat_map_iter(|chunk| chunk.iter().map(|line| parse_statement(line)))
+            .collect();
+
+        Self::from_statements(filename, statements)
+    }
+
+    /// Axis-aligned cube centered on the origin with edges `size` long: 8 vertices and 12
+    /// triangles, two per side, each side with its own normal and the full [0, 1] uv square.
+    #[allow(dead_code)]
+    pub fn cube(size: f32) -> Model {
+        let h = size / 2.0;
+        let verts: Vec<Vec3f> = (0..8)
+            .map(|i| {
+                let coord = |bit: usize| if i & bit != 0 { h } else { -h };
+                Vec3f::new(coord(1), coord(2), coord(4))
+            })
+            .collect();
+        let square = [(0, 0), (1, 0), (1, 1), (0, 1)];
+        let uvs = square.map(|(u, v)| Vec2f::new(u as f32, v as f32));
+
+        let mut normals = Vec::new();
+        let mut faces = Vec::new();
+        for axis in 0..3 {
+            for side in [0, 1] {
+                let mut normal = [0.0; 3];
+                normal[axis] = if side == 1 { 1.0 } else { -1.0 };
+                let n = normals.len();
+                normals.push(Vec3f::new(normal[0], normal[1], normal[2]));
+                // The next two axes span the side, counter-clockwise around the positive axis
+                let (a, b) = (1 << ((axis + 1) % 3), 1 << ((axis + 2) % 3));
+                let corners = square.map(|(u, v)| (side << axis) | (u * a) | (v * b));
+                let tris = if side == 1 {
+                    [[0, 1, 2], [0, 2, 3]]
+                } else {
+                    [[0, 2, 1], [0, 3, 2]]
+                };
+                for tri in tris {
+                    faces.push(tri.map(|k| [corners[k], k, n]));
+                }
+            }
+        }
+        Self::from_triangles(verts, uvs.to_vec(), normals, faces)
+    }
+
+    /// Square in the xy plane centered on the origin and facing +z, with sides `size` long and
+    /// split into `subdivisions` cells along each side (at least 1), each made of two
+    /// triangles. uvs span [0, 1] across the plane.
+    #[allow(dead_code)]
+    pub fn plane(size: f32, subdivisions: u32) -> Model {
+        let n = subdivisions.max(1) as usize;
+        let idx = |i: usize, j: usize| i + j * (n + 1);
+        let mut verts = Vec::new();
+        let mut uvs = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                verts.push(Vec3f::new((u - 0.5) * size, (v - 0.5) * size, 0.0));
+                uvs.push(Vec2f::new(u, v));
+            }
+        }
+
+        let mut faces = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                let quad = [idx(i, j), idx(i + 1, j), idx(i + 1, j + 1), idx(i, j + 1)];
+                for tri in [[0, 1, 2], [0, 2, 3]] {
+                    faces.push(tri.map(|k| [quad[k], quad[k], 0]));
+                }
+            }
+        }
+        Self::from_triangles(verts, uvs, vec![Vec3f::new(0.0, 0.0, 1.0)], faces)
+    }
+
+    /// UV sphere centered on the origin, with `segments` slices around the y axis (at least 3)
+    /// and half as many stacks from pole to pole (at least 2). Each pole is a single vertex,
+    /// normals point outwards from the center and uvs wrap once around in u, with v = 1 at the
+    /// top.
+    #[allow(dead_code)]
+    pub fn sphere(radius: f32, segments: u32) -> Model {
+        let slices = segments.max(3) as usize;
+        let stacks = (slices / 2).max(2);
+        let mut verts = vec![Vec3f::new(0.0, radius, 0.0)];
+        for k in 1..stacks {
+            let (sin_theta, cos_theta) =
+                (std::f32::consts::PI * k as f32 / stacks as f32).sin_cos();
+            for s in 0..slices {
+                let (sin_phi, cos_phi) =
+                    (std::f32::consts::TAU * s as f32 / slices as f32).sin_cos();
+                verts
+                    .push(Vec3f::new(sin_theta * sin_phi, cos_theta, sin_theta * cos_phi) * radius);
+            }
+        }
+        verts.push(Vec3f::new(0.0, -radius, 0.0));
+        let normals = verts.iter().map(|v| v.normalize(1.0)).collect();
+        let mut uvs = Vec::new();
+        for k in 0..=stacks {
+            for s in 0..=slices {
+                uvs.push(Vec2f::new(
+                    s as f32 / slices as f32,
+                    1.0 - k as f32 / stacks as f32,
+                ));
+            }
+        }
 
-        for line_result in reader.lines() {
-            let line = match line_result {
-                Ok(line) => line,
-                Err(e) => return Err(e.to_string()),
+        // Corner at stack boundary `k` and slice boundary `s`; the seam repeats uvs, not vertices
+        let last = verts.len() - 1;
+        let corner = |k: usize, s: usize| {
+            let vert = match k {
+                0 => 0,
+                k if k == stacks => last,
+                k => 1 + (k - 1) * slices + s % slices,
             };
+            [vert, s + k * (slices + 1), vert]
+        };
+        let mut faces = Vec::new();
+        for k in 0..stacks {
+            for s in 0..slices {
+                let quad = [
+                    corner(k, s),
+                    corner(k + 1, s),
+                    corner(k + 1, s + 1),
+                    corner(k, s + 1),
+                ];
+                // Quads touching a pole collapse to one triangle
+                if k + 1 < stacks {
+                    faces.push([quad[0], quad[1], quad[2]]);
+                }
+                if k > 0 {
+                    faces.push([quad[0], quad[2], quad[3]]);
+                }
+            }
+        }
+        Self::from_triangles(verts, uvs, normals, faces)
+    }
 
-            match line.split_whitespace().next() {
-                Some("v") => {
-                    let mut parts = line[2..].split_whitespace();
-                    let x = parse_coordinate(&mut parts, "Failed to parse x coordinate")?;
-                    let y = parse_coordinate(&mut parts, "Failed to parse y coordinate")?;
-                    let z = parse_coordinate(&mut parts, "Failed to parse x coordinate")?;
-                    verts.push(Vec3f::new(x, y, z));
+    /// Builds a generated model from its triangles, given as `[vertex, uv, normal]` corners
+    /// counter-clockwise from the outside.
+    fn from_triangles(
+        verts: Vec<Vec3f>,
+        uvs: Vec<Vec2f>,
+        normals: Vec<Vec3f>,
+        faces: Vec<[[usize; 3]; 3]>,
+    ) -> Model {
+        let statements = verts
+            .into_iter()
+            .map(Statement::Vertex)
+            .chain(uvs.into_iter().map(Statement::Uv))
+            .chain(normals.into_iter().map(Statement::Normal))
+            .chain(faces.into_iter().map(|face| Statement::Face(face.to_vec())))
+            .map(Ok);
+        Self::from_statements("", statements).expect("Generated meshes are valid")
+    }
+
+    /// Builds a model from the parsed lines of `filename`, in file order. Groups, smoothing
+    /// groups and materials depend on the statements before them, so this part is sequential.
+    fn from_statements(
+        filename: &str,
+        statements: impl IntoIterator<Item = Result<Statement, String>>,
+    ) -> Result<Self, String> {
+        let mut verts: Vec<Vec3f> = Vec::new();
+        let mut uvs: Vec<Vec2f> = Vec::new();
+        let mut normals: Vec<Vec3f> = Vec::new();
+        let mut corners: Vec<[usize; 3]> = Vec::new();
+        let mut faces: Vec<Range<usize>> = Vec::new();
+        let mut lines: Vec<Vec<usize>> = Vec::new();
+        let mut param_verts: Vec<Vec3f> = Vec::new();
+        let mut groups: Vec<(String, Range<usize>)> = Vec::new();
+        let mut group_name = String::from("default");
+        let mut group_start = 0;
+        let mut smoothing_groups: Vec<Option<u32>> = Vec::new();
+        let mut smoothing_group: Option<u32> = None;
+        let mut material_libs: Vec<String> = Vec::new();
+        let mut used_material: Option<String> = None;
+        // Names selected by `usemtl`, and the position in it of each face's material
+        let mut material_names: Vec<String> = Vec::new();
+        let mut current_material: Option<usize> = None;
+        let mut face_material_names: Vec<Option<usize>> = Vec::new();
+
+        for statement in statements {
+            match statement? {
+                Statement::Vertex(v) => verts.push(v),
+                Statement::Uv(uv) => uvs.push(uv),
+                Statement::Normal(n) => normals.push(n),
+                Statement::ParamVertex(vp) => param_verts.push(vp),
+                Statement::Face(face) => {
+                    if face.len() < 3 {
+                        return Err(format!(
+                            "Face {} has {} vertices, but at least 3 are needed",
+                            faces.len() + 1,
+                            face.len()
+                        ));
+                    }
+                    let start = corners.len();
+                    corners.extend(face);
+                    faces.push(start..corners.len());
+                    smoothing_groups.push(smoothing_group);
+                    face_material_names.push(current_material);
                 }
-                Some("f") => {
-                    let mut face = Vec::new();
-                    let parts = line[2..].split_whitespace();
-                    for part in parts {
-                        let mut idxs = Vec::new();
-                        for idx in part.split('/') {
-                            match idx.parse::<usize>() {
-                                Ok(idx) => idxs.push(idx - 1), // OBJ index starts from 1
-                                Err(e) => return Err(e.to_string()),
-                            }
-                        }
-                        face.push(idxs);
+                Statement::Line(line) => lines.push(line),
+                Statement::Group(name) => {
+                    if faces.len() > group_start {
+                        groups.push((group_name, group_start..faces.len()));
                     }
-                    faces.push(face);
+                    group_name = name;
+                    group_start = faces.len();
                 }
-                Some("vt") => {
-                    let mut parts = line[2..].split_whitespace();
-                    let u = parse_coordinate(&mut parts, "Failed to parse u coordinate")?;
-                    let v = parse_coordinate(&mut parts, "Failed to parse v coordinate")?;
-                    uvs.push(Vec2f::new(u, v));
+                Statement::Smoothing(group) => smoothing_group = Some(group),
+                Statement::MaterialLibs(libs) => material_libs.extend(libs),
+                Statement::UseMaterial(name) => {
+                    if used_material.is_none() {
+                        used_material.clone_from(&name);
+                    }
+                    current_material = name.map(|name| {
+                        material_names
+                            .iter()
+                            .position(|n| *n == name)
+                            .unwrap_or_else(|| {
+                                material_names.push(name);
+                                material_names.len() - 1
+                            })
+                    });
                 }
-                Some("vn") => {
-                    let mut parts = line[2..].split_whitespace();
-                    let nx = parse_coordinate(&mut parts, "Failed to parse nx coordinate")?;
-                    let ny = parse_coordinate(&mut parts, "Failed to parse ny coordinate")?;
-                    let nz = parse_coordinate(&mut parts, "Failed to parse nx coordinate")?;
-                    normals.push(Vec3f::new(nx, ny, nz));
+                Statement::Ignored => continue,
+            }
+        }
+
+        // Material libraries are named relative to the OBJ file
+        let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+        // An unreadable library only costs its materials; rendering reports it later
+        let mut materials = Vec::new();
+        let mut missing_material_libs = Vec::new();
+        for lib in &material_libs {
+            let path = dir.join(lib);
+            match load_mtl(&path) {
+                Ok(lib_materials) => materials.extend(lib_materials),
+                Err(_) => missing_material_libs.push(path),
+            }
+        }
+        // Names no library defines leave their faces without a material
+        let name_materials: Vec<Option<usize>> = material_names
+            .iter()
+            .map(|name| materials.iter().position(|m| &m.name == name))
+            .collect();
+        let face_materials = face_material_names
+            .into_iter()
+            .map(|name| name.and_then(|k| name_materials[k]))
+            .collect();
+
+        // Close the last open group. Empty groups (e.g. an `o` immediately followed by a `g`) are dropped.
+        if faces.len() > group_start {
+            groups.push((group_name, group_start..faces.len()));
+        }
+
+        for (i, face) in faces.iter().enumerate() {
+            for idxs in &corners[face.clone()] {
+                for (kind, idx, count) in [
+                    ("vertex", idxs[0], verts.len()),
+                    ("uv", idxs[1], uvs.len()),
+                    ("normal", idxs[2], normals.len()),
+                ] {
+                    if idx != NO_INDEX && idx >= count {
+                        return Err(format!(
+                            "Face {} references {} {}, but only {} are defined",
+                            i + 1,
+                            kind,
+                            idx + 1,
+                            count
+                        ));
+                    }
                 }
-                Some(&_) => continue,
-                None => continue,
-            };
+            }
         }
 
-        let model = Model {
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(&idx) = line.iter().find(|&&idx| idx >= verts.len()) {
+                return Err(format!(
+                    "Line {} references vertex {}, but only {} are defined",
+                    i + 1,
+                    idx + 1,
+                    verts.len()
+                ));
+            }
+        }
+
+        Ok(Model {
             verts,
             uvs,
             normals,
+            corners,
             faces,
-        };
+            lines,
+            param_verts,
+            groups,
+            smoothing_groups,
+            materials,
+            missing_material_libs,
+            used_material,
+            face_materials,
+            face_normals: RefCell::new(None),
+        })
+    }
 
-        println!(
-            "Model loaded. verts: {}, uvs: {}, normals: {}, faces: {}",
-            model.verts.len(),
-            model.uvs.len(),
-            model.normals.len(),
-            model.faces.len()
-        );
+    /// Writes the model as an OBJ file, including its `o` groups.
+    #[allow(dead_code)]
+    pub fn save_obj(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+
+        for v in &self.verts {
+            writeln!(out, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+        for uv in &self.uvs {
+            writeln!(out, "vt {} {}", uv.x, uv.y)?;
+        }
+        for n in &self.normals {
+            writeln!(out, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+
+        for (i, face) in self.faces_iter().enumerate() {
+            if let Some((name, _)) = self.groups.iter().find(|(_, range)| range.start == i) {
+                writeln!(out, "o {}", name)?;
+            }
+            let corners: Vec<String> = face
+                .iter()
+                .map(|idxs| {
+                    // OBJ index starts from 1; trailing missing indices are left out and inner
+                    // ones left empty, as in `v//vn`
+                    let len = idxs
+                        .iter()
+                        .rposition(|&idx| idx != NO_INDEX)
+                        .map_or(0, |k| k + 1);
+                    idxs[..len]
+                        .iter()
+                        .map(|&idx| match idx {
+                            NO_INDEX => String::new(),
+                            idx => (idx + 1).to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("/")
+                })
+                .collect();
+            writeln!(out, "f {}", corners.join(" "))?;
+        }
 
-        Ok(model)
+        out.flush()
     }
 
     #[allow(dead_code)]
@@ -112,17 +492,464 @@ impl Model {
         self.verts[idx]
     }
 
+    #[allow(dead_code)]
     pub fn uv(&self, idx: usize) -> Vec2f {
         self.uvs[idx]
     }
 
+    #[allow(dead_code)]
     pub fn normal(&self, idx: usize) -> Vec3f {
         self.normals[idx]
     }
 
-    pub fn face(&self, idx: usize) -> &Vec<Vec<usize>> {
-        &self.faces[idx]
+    #[allow(dead_code)]
+    pub fn try_vert(&self, idx: usize) -> Option<Vec3f> {
+        self.verts.get(idx).copied()
+    }
+
+    #[allow(dead_code)]
+    pub fn try_uv(&self, idx: usize) -> Option<Vec2f> {
+        self.uvs.get(idx).copied()
+    }
+
+    #[allow(dead_code)]
+    pub fn try_normal(&self, idx: usize) -> Option<Vec3f> {
+        self.normals.get(idx).copied()
+    }
+
+    /// `[vertex, uv, normal]` indices of the corners of face `idx`, with `NO_INDEX` for a uv
+    /// or normal the file leaves out.
+    pub fn face(&self, idx: usize) -> &[[usize; 3]] {
+        &self.corners[self.faces[idx].clone()]
+    }
+
+    #[allow(dead_code)]
+    pub fn verts(&self) -> &[Vec3f] {
+        &self.verts
+    }
+
+    #[allow(dead_code)]
+    pub fn uvs(&self) -> &[Vec2f] {
+        &self.uvs
+    }
+
+    #[allow(dead_code)]
+    pub fn normals(&self) -> &[Vec3f] {
+        &self.normals
+    }
+
+    #[allow(dead_code)]
+    pub fn faces_iter(&self) -> impl Iterator<Item = &[[usize; 3]]> {
+        self.faces.iter().map(|range| &self.corners[range.clone()])
+    }
+
+    /// Winding of face `idx` relative to the average of its stored vertex normals. Faces without
+    /// normals have nothing to be judged against and are projected onto +z instead, i.e. judged
+    /// by their signed area in the xy plane, which says nothing about whether they face outwards.
+    #[allow(dead_code)]
+    pub fn face_winding(&self, idx: usize) -> Winding {
+        let geometric = self.geometric_normal(idx);
+
+        let mut reference = Vec3f::new(0.0, 0.0, 0.0);
+        for idxs in self.face(idx) {
+            if let Some(n) = self.try_normal(idxs[2]) {
+                reference = reference + n;
+            }
+        }
+        if reference.norm() == 0.0 {
+            reference = Vec3f::new(0.0, 0.0, 1.0);
+        }
+
+        if geometric.dot(reference) < 0.0 {
+            Winding::Clockwise
+        } else {
+            Winding::CounterClockwise
+        }
+    }
+
+    /// Reverses every face whose geometric normal disagrees with its stored vertex normals, so
+    /// all faces wind counter-clockwise around them. Faces without normals are left alone.
+    /// Returns the number of faces flipped.
+    #[allow(dead_code)]
+    pub fn unify_winding(&mut self) -> usize {
+        let mut flipped = 0;
+        for i in 0..self.faces.len() {
+            let has_normals = self
+                .face(i)
+                .iter()
+                .any(|idxs| self.try_normal(idxs[2]).is_some());
+            if has_normals && self.face_winding(i) == Winding::Clockwise {
+                self.corners[self.faces[i].clone()].reverse();
+                flipped += 1;
+            }
+        }
+        if flipped > 0 {
+            *self.face_normals.get_mut() = None;
+        }
+        flipped
+    }
+
+    /// Merges vertices closer than `epsilon` to an earlier vertex into it, rewriting face and
+    /// polyline indices, and drops the merged vertices. Surviving vertices keep their order.
+    /// Faces that end up with repeated vertices are kept; they rasterize as degenerate. An
+    /// epsilon of 0 or less merges nothing.
+    #[allow(dead_code)]
+    pub fn weld_vertices(&mut self, epsilon: f32) {
+        if epsilon <= 0.0 {
+            return;
+        }
+        // Canonical vertices bucketed by the epsilon-sized grid cell they fall in, so only the
+        // neighbouring cells need to be searched
+        let cell = |v: Vec3f| {
+            let c = |x: f32| (x / epsilon).floor() as i64;
+            (c(v.x), c(v.y), c(v.z))
+        };
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut verts: Vec<Vec3f> = Vec::new();
+        let mut remap = Vec::with_capacity(self.verts.len());
+        for &v in &self.verts {
+            let (x, y, z) = cell(v);
+            let existing = (-1..=1)
+                .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (dx, dy, dz))))
+                .filter_map(|(dx, dy, dz)| grid.get(&(x + dx, y + dy, z + dz)))
+                .flatten()
+                .copied()
+                .find(|&i| (verts[i] - v).norm() < epsilon);
+            remap.push(existing.unwrap_or_else(|| {
+                grid.entry((x, y, z)).or_default().push(verts.len());
+                verts.push(v);
+                verts.len() - 1
+            }));
+        }
+
+        for corner in &mut self.corners {
+            corner[0] = remap[corner[0]];
+        }
+        for line in &mut self.lines {
+            for idx in line {
+                *idx = remap[*idx];
+            }
+        }
+        self.verts = verts;
+        *self.face_normals.get_mut() = None;
+    }
+
+    /// Recomputes vertex normals from the face geometry. Faces sharing a vertex are smoothed
+    /// together only if their normals are within `smoothing_angle` degrees of each other, so
+    /// sharper edges keep per-face normals. Once the file uses `s` statements, faces must also
+    /// share a smoothing group, and faces with smoothing off are never smoothed.
+    #[allow(dead_code)]
+    pub fn compute_vertex_normals(&mut self, smoothing_angle: f32) {
+        let face_normals: Vec<Vec3f> = (0..self.faces.len()).map(|i| self.face_normal(i)).collect();
+        let mut vert_faces = vec![Vec::new(); self.verts.len()];
+        for (i, face) in self.faces_iter().enumerate() {
+            for idxs in face {
+                vert_faces[idxs[0]].push(i);
+            }
+        }
+
+        let threshold = smoothing_angle.to_radians().cos();
+        let mut normals: Vec<Vec3f> = Vec::new();
+        // Normals already emitted for each vertex, so identical corners share an index
+        let mut vert_normals: Vec<Vec<usize>> = vec![Vec::new(); self.verts.len()];
+
+        for (i, face) in self.faces.iter().enumerate() {
+            for idxs in &mut self.corners[face.clone()] {
+                let v = idxs[0];
+                let mut sum = Vec3f::new(0.0, 0.0, 0.0);
+                for &j in &vert_faces[v] {
+                    let same_group = match (self.smoothing_groups[i], self.smoothing_groups[j]) {
+                        (None, None) => true,
+                        (gi, gj) => i == j || (gi == gj && gi != Some(0)),
+                    };
+                    if same_group && face_normals[i].dot(face_normals[j]) >= threshold {
+                        sum = sum + face_normals[j];
+                    }
+                }
+                let n = sum.normalize(1.0);
+
+                let existing = vert_normals[v]
+                    .iter()
+                    .copied()
+                    .find(|&k| (normals[k] - n).norm() < 1e-6);
+                let normal_idx = existing.unwrap_or_else(|| {
+                    normals.push(n);
+                    vert_normals[v].push(normals.len() - 1);
+                    normals.len() - 1
+                });
+
+                idxs[2] = normal_idx;
+            }
+        }
+
+        self.normals = normals;
+    }
+
+    /// Unit normal of face `idx` from its first three vertices, pointing to the side the face
+    /// winds counter-clockwise around. Normals for all faces are computed once and cached.
+    pub fn face_normal(&self, idx: usize) -> Vec3f {
+        self.face_normals.borrow_mut().get_or_insert_with(|| {
+            (0..self.faces.len())
+                .map(|i| self.geometric_normal(i).normalize(1.0))
+                .collect()
+        })[idx]
+    }
+
+    fn geometric_normal(&self, idx: usize) -> Vec3f {
+        let face = self.face(idx);
+        let v0 = self.verts[face[0][0]];
+        let v1 = self.verts[face[1][0]];
+        let v2 = self.verts[face[2][0]];
+        (v1 - v0).cross(v2 - v0)
+    }
+
+    /// Counts of the model's elements and the bounds of its vertices.
+    #[allow(dead_code)]
+    pub fn stats(&self) -> ModelStats {
+        let bounding_box =
+            self.verts
+                .iter()
+                .skip(1)
+                .fold(self.verts.first().map(|&v| (v, v)), |bounds, &v| {
+                    bounds.map(|(min, max)| {
+                        (
+                            Vec3f::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z)),
+                            Vec3f::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z)),
+                        )
+                    })
+                });
+        ModelStats {
+            verts: self.verts.len(),
+            uvs: self.uvs.len(),
+            normals: self.normals.len(),
+            faces: self.faces.len(),
+            triangles: self.faces.iter().map(|face| face.len() - 2).sum(),
+            bounding_box,
+        }
     }
+
+    /// Materials defined by the model's `mtllib` files.
+    #[allow(dead_code)]
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// `mtllib` files named by the model that could not be read.
+    #[allow(dead_code)]
+    pub fn missing_material_libs(&self) -> &[PathBuf] {
+        &self.missing_material_libs
+    }
+
+    /// Path of the diffuse texture of the first material the model uses, falling back to the
+    /// first material defined.
+    #[allow(dead_code)]
+    pub fn diffuse_map(&self) -> Option<&Path> {
+        let material = match &self.used_material {
+            Some(name) => self.materials.iter().find(|m| &m.name == name),
+            None => self.materials.first(),
+        };
+        material?.diffuse_map.as_deref()
+    }
+
+    /// Index into `materials` of the material face `idx` was assigned by `usemtl`, if any.
+    #[allow(dead_code)]
+    pub fn face_material(&self, idx: usize) -> Option<usize> {
+        self.face_materials[idx]
+    }
+
+    /// Polylines from `l` statements, as lists of vertex indices.
+    #[allow(dead_code)]
+    pub fn lines(&self) -> &[Vec<usize>] {
+        &self.lines
+    }
+
+    /// Parameter space vertices from `vp` statements, as (u, v, w).
+    #[allow(dead_code)]
+    pub fn param_verts(&self) -> &[Vec3f] {
+        &self.param_verts
+    }
+
+    /// Named groups (`o` / `g` statements) with the range of face indices they cover.
+    #[allow(dead_code)]
+    pub fn groups(&self) -> &[(String, Range<usize>)] {
+        &self.groups
+    }
+
+    /// Corners of the faces in group `name`, face after face. The faces are stored contiguously,
+    /// so the group's range in `groups` splits the slice back into faces.
+    #[allow(dead_code)]
+    pub fn group_faces(&self, name: &str) -> Option<&[[usize; 3]]> {
+        let (_, range) = self
+            .groups
+            .iter()
+            .find(|(group_name, _)| group_name == name)?;
+        if range.is_empty() {
+            return Some(&[]);
+        }
+        Some(&self.corners[self.faces[range.start].start..self.faces[range.end - 1].end])
+    }
+}
+
+/// Parses one OBJ line. Indices are converted to 0-based but not checked against the data
+/// defined so far.
+fn parse_statement(line: &str) -> Result<Statement, String> {
+    let parse_coordinate = |parts: &mut SplitWhitespace<'_>, name: &str| {
+        parts
+            .next()
+            .ok_or(format!("Missing {}", name))?
+            .parse::<f32>()
+            .map_err(|_| format!("Failed to parse {}", name))
+    };
+
+    // Anything after `#` is a comment
+    let content = line.split_once('#').map_or(line, |(data, _)| data);
+    let mut parts = content.split_whitespace();
+
+    let statement = match parts.next() {
+        Some("v") => {
+            let x = parse_coordinate(&mut parts, "x coordinate")?;
+            let y = parse_coordinate(&mut parts, "y coordinate")?;
+            let z = parse_coordinate(&mut parts, "z coordinate")?;
+            Statement::Vertex(Vec3f::new(x, y, z))
+        }
+        Some("f") => {
+            let mut face = Vec::new();
+            for part in parts {
+                let mut idxs = [NO_INDEX; 3];
+                for (k, idx) in part.split('/').enumerate() {
+                    if k == 3 {
+                        return Err(format!("Face corner {} has too many indices", part));
+                    }
+                    // `v//vn` leaves the uv out
+                    if k > 0 && idx.is_empty() {
+                        continue;
+                    }
+                    match idx.parse::<usize>() {
+                        // OBJ index starts from 1
+                        Ok(idx) => match idx.checked_sub(1) {
+                            Some(idx) => idxs[k] = idx,
+                            None => return Err("Face index 0 is invalid".to_string()),
+                        },
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+                face.push(idxs);
+            }
+            Statement::Face(face)
+        }
+        Some("l") => {
+            let mut line = Vec::new();
+            for part in parts {
+                // Only the vertex index matters; a `v/vt` uv index is ignored
+                let idx = part.split('/').next().unwrap_or(part);
+                match idx.parse::<usize>() {
+                    // OBJ index starts from 1
+                    Ok(idx) => match idx.checked_sub(1) {
+                        Some(idx) => line.push(idx),
+                        None => return Err("Line index 0 is invalid".to_string()),
+                    },
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            Statement::Line(line)
+        }
+        Some("vp") => {
+            let u = parse_coordinate(&mut parts, "u parameter")?;
+            // v and w are optional and default to 0 and 1
+            let v = match parts.next() {
+                Some(v) => v.parse().map_err(|_| "Failed to parse v parameter")?,
+                None => 0.0,
+            };
+            let w = match parts.next() {
+                Some(w) => w.parse().map_err(|_| "Failed to parse w parameter")?,
+                None => 1.0,
+            };
+            Statement::ParamVertex(Vec3f::new(u, v, w))
+        }
+        Some("vt") => {
+            let u = parse_coordinate(&mut parts, "u coordinate")?;
+            let v = parse_coordinate(&mut parts, "v coordinate")?;
+            Statement::Uv(Vec2f::new(u, v))
+        }
+        Some("vn") => {
+            let nx = parse_coordinate(&mut parts, "nx coordinate")?;
+            let ny = parse_coordinate(&mut parts, "ny coordinate")?;
+            let nz = parse_coordinate(&mut parts, "nz coordinate")?;
+            Statement::Normal(Vec3f::new(nx, ny, nz))
+        }
+        Some("o") | Some("g") => {
+            let name = parts.collect::<Vec<_>>().join(" ");
+            Statement::Group(if name.is_empty() {
+                "default".to_string()
+            } else {
+                name
+            })
+        }
+        Some("s") => Statement::Smoothing(match parts.next() {
+            Some("off") => 0,
+            Some(n) => n
+                .parse::<u32>()
+                .map_err(|_| "Failed to parse smoothing group")?,
+            None => return Err("Missing smoothing group".to_string()),
+        }),
+        Some("mtllib") => Statement::MaterialLibs(parts.map(str::to_string).collect()),
+        Some("usemtl") => Statement::UseMaterial(parts.next().map(str::to_string)),
+        Some(&_) => Statement::Ignored,
+        None => Statement::Ignored,
+    };
+
+    Ok(statement)
+}
+
+/// Parses the materials of an MTL file. Texture paths are resolved relative to its directory.
+fn load_mtl(path: &Path) -> Result<Vec<Material>, String> {
+    let Ok(file) = File::open(path) else {
+        return Err(format!(
+            "Failed to open material library {}",
+            path.display()
+        ));
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials: Vec<Material> = Vec::new();
+
+    for line_result in BufReader::new(file).lines() {
+        let line = line_result.map_err(|e| e.to_string())?;
+        let content = line.split_once('#').map_or(line.as_str(), |(data, _)| data);
+        let mut parts = content.split_whitespace();
+
+        match parts.next() {
+            Some("newmtl") => materials.push(Material {
+                name: parts.collect::<Vec<_>>().join(" "),
+                diffuse_map: None,
+                diffuse_color: [1.0; 3],
+                specular_color: [0.0; 3],
+            }),
+            Some(key @ ("Kd" | "Ks")) => {
+                let mut color = [0.0; 3];
+                for c in &mut color {
+                    *c = parts
+                        .next()
+                        .and_then(|c| c.parse().ok())
+                        .ok_or(format!("Failed to parse {} color", key))?;
+                }
+                if let Some(material) = materials.last_mut() {
+                    match key {
+                        "Kd" => material.diffuse_color = color,
+                        _ => material.specular_color = color,
+                    }
+                }
+            }
+            // Options such as `-s` precede the file name, which comes last
+            Some("map_Kd") => {
+                if let (Some(material), Some(file)) = (materials.last_mut(), parts.last()) {
+                    material.diffuse_map = Some(dir.join(file));
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    Ok(materials)
 }
 
 #[cfg(test)]
@@ -134,9 +961,375 @@ mod tests {
         let model = Model::new("tests/models/sample.obj").expect("Failed to load model.");
 
         // In the object file form, 1/1/1/2/1 3/3/1, but since the index is subtracted by 1 when storing the data, the assertion is made with the value of 1 subtracted.
+        assert_eq!(model.face(0), [[0, 0, 0], [1, 1, 0], [2, 2, 0]]);
+    }
+
+    #[test]
+    fn test_stats() {
+        let model = Model::new("tests/models/quad.obj").unwrap();
+        let stats = model.stats();
+        assert_eq!(
+            (stats.verts, stats.uvs, stats.normals),
+            (model.nverts(), 1, 1)
+        );
+        assert_eq!(stats.faces, 1);
+        assert_eq!(stats.triangles, 2);
+        let (min, max) = stats.bounding_box.unwrap();
+        assert_eq!((min.x, min.y, min.z), (-1.0, -1.0, 0.0));
+        assert_eq!((max.x, max.y, max.z), (1.0, 1.0, 0.0));
+    }
+
+    /// Loads a model between two markers on stdout, for `test_load_is_silent` to inspect.
+    #[test]
+    #[ignore = "run in a child process by test_load_is_silent"]
+    fn load_between_markers() {
+        print!("<<");
+        Model::new("tests/models/quad.obj").unwrap();
+        print!(">>");
+    }
+
+    #[test]
+    fn test_load_is_silent() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "model::tests::load_between_markers",
+                "--exact",
+                "--ignored",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("<<>>"), "stdout = {:?}", stdout);
+    }
+
+    #[test]
+    fn test_groups() {
+        let model = Model::new("tests/models/groups.obj").expect("Failed to load model.");
+
+        assert_eq!(
+            model.groups(),
+            &[("first".to_string(), 0..2), ("second".to_string(), 2..3)]
+        );
+        assert_eq!(
+            model.group_faces("first").unwrap(),
+            [model.face(0), model.face(1)].concat()
+        );
+        assert_eq!(model.group_faces("second").unwrap(), model.face(2));
+        assert!(model.group_faces("missing").is_none());
+    }
+
+    #[test]
+    fn test_out_of_range_face_index() {
+        let err = Model::new("tests/models/out_of_range.obj")
+            .err()
+            .expect("Out-of-range index should be rejected.");
+        assert_eq!(err, "Face 1 references vertex 999, but only 3 are defined");
+    }
+
+    #[test]
+    fn test_short_lines() {
+        let err = Model::new("tests/models/bare_vertex.obj")
+            .err()
+            .expect("A vertex without coordinates should be rejected.");
+        assert_eq!(err, "Missing x coordinate");
+
+        let err = Model::new("tests/models/bare_face.obj")
+            .err()
+            .expect("A face without vertices should be rejected.");
+        assert_eq!(err, "Face 1 has 0 vertices, but at least 3 are needed");
+
+        // The final `f` line is not terminated by a newline.
+        let model =
+            Model::new("tests/models/no_trailing_newline.obj").expect("Failed to load model.");
+        assert_eq!(model.nfaces(), 1);
+        assert_eq!(
+            model.face(0),
+            [
+                [0, NO_INDEX, NO_INDEX],
+                [1, NO_INDEX, NO_INDEX],
+                [2, NO_INDEX, NO_INDEX]
+            ]
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_new_parallel() {
+        for path in [
+            "tests/models/sample.obj",
+            "tests/models/groups.obj",
+            "tests/models/smoothing.obj",
+        ] {
+            let serial = Model::new(path).expect("Failed to load model.");
+            let parallel = Model::new_parallel(path).expect("Failed to load model.");
+
+            for (a, b) in [
+                (&serial.verts, &parallel.verts),
+                (&serial.normals, &parallel.normals),
+                (&serial.param_verts, &parallel.param_verts),
+            ] {
+                assert_eq!(a.len(), b.len());
+                assert!(a.iter().zip(b).all(|(a, b)| a.approx_eq(b, 0.0)));
+            }
+            assert_eq!(serial.uvs.len(), parallel.uvs.len());
+            assert!(serial
+                .uvs
+                .iter()
+                .zip(&parallel.uvs)
+                .all(|(a, b)| a.approx_eq(b, 0.0)));
+            assert_eq!(serial.corners, parallel.corners);
+            assert_eq!(serial.faces, parallel.faces);
+            assert_eq!(serial.lines, parallel.lines);
+            assert_eq!(serial.groups, parallel.groups);
+            assert_eq!(serial.smoothing_groups, parallel.smoothing_groups);
+            assert_eq!(serial.materials, parallel.materials);
+            assert_eq!(serial.used_material, parallel.used_material);
+        }
+    }
+
+    #[test]
+    fn test_try_accessors() {
+        let model = Model::new("tests/models/sample.obj").expect("Failed to load model.");
+        assert!(model.try_vert(0).is_some());
+        assert!(model.try_vert(model.nverts()).is_none());
+        assert!(model.try_uv(usize::MAX).is_none());
+        assert!(model.try_normal(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_unify_winding() {
+        let mut model = Model::new("tests/models/winding.obj").expect("Failed to load model.");
+        assert_eq!(model.face_winding(0), Winding::CounterClockwise);
+        assert_eq!(model.face_winding(1), Winding::Clockwise);
+
+        assert_eq!(model.unify_winding(), 1);
+        assert_eq!(model.face_winding(1), Winding::CounterClockwise);
+        assert_eq!(model.face(1), [[2, 0, 0], [1, 0, 0], [3, 0, 0]]);
+        assert_eq!(model.unify_winding(), 0);
+
+        // A closed mesh wound outwards without vn lines, with a face looking down -z
+        let mut model = Model::new("tests/models/tetrahedron.obj").expect("Failed to load model.");
+        let faces: Vec<_> = model.faces_iter().map(|f| f.to_vec()).collect();
+        assert_eq!(model.unify_winding(), 0);
+        assert!(model.faces_iter().map(|f| f.to_vec()).eq(faces));
+    }
+
+    #[test]
+    fn test_weld_vertices() {
+        let mut model = Model::new("tests/models/weld.obj").expect("Failed to load model.");
+        model.weld_vertices(1e-4);
+        assert_eq!(model.nverts(), 4);
+        assert_eq!(
+            model.face(0),
+            [
+                [0, NO_INDEX, NO_INDEX],
+                [1, NO_INDEX, NO_INDEX],
+                [2, NO_INDEX, NO_INDEX]
+            ]
+        );
+        assert_eq!(
+            model.face(1),
+            [
+                [0, NO_INDEX, NO_INDEX],
+                [2, NO_INDEX, NO_INDEX],
+                [3, NO_INDEX, NO_INDEX]
+            ]
+        );
+        assert_eq!(model.vert(3).y, 1.0);
+
+        // Nothing is close enough below the offset of the near duplicate
+        let mut model = Model::new("tests/models/weld.obj").expect("Failed to load model.");
+        model.weld_vertices(1e-8);
+        assert_eq!(model.nverts(), 5);
+    }
+
+    #[test]
+    fn test_primitives() {
+        // Every face of a convex mesh around the origin faces away from it
+        let faces_outwards = |model: &Model| {
+            (0..model.nfaces()).all(|i| {
+                let center = model
+                    .face(i)
+                    .iter()
+                    .fold(Vec3f::new(0.0, 0.0, 0.0), |sum, c| sum + model.vert(c[0]));
+                model.face_normal(i).dot(center) > 0.0
+            })
+        };
+
+        let cube = Model::cube(2.0);
+        let stats = cube.stats();
+        assert_eq!((stats.verts, stats.faces, stats.triangles), (8, 12, 12));
+        let (min, max) = stats.bounding_box.unwrap();
+        assert!(min.approx_eq(&Vec3f::new(-1.0, -1.0, -1.0), 1e-6));
+        assert!(max.approx_eq(&Vec3f::new(1.0, 1.0, 1.0), 1e-6));
+        assert!(faces_outwards(&cube));
+        for i in 0..cube.nfaces() {
+            let n = cube.normal(cube.face(i)[0][2]);
+            assert!(n.approx_eq(&cube.face_normal(i), 1e-6), "face {}", i);
+        }
+
+        let plane = Model::plane(2.0, 4);
+        assert_eq!((plane.nverts(), plane.nfaces()), (25, 32));
+        assert!((0..plane.nfaces()).all(|i| plane
+            .face_normal(i)
+            .approx_eq(&Vec3f::new(0.0, 0.0, 1.0), 1e-6)));
+
+        let sphere = Model::sphere(1.5, 16);
+        assert!(sphere.verts().iter().all(|v| (v.norm() - 1.5).abs() < 1e-5));
+        assert_eq!(sphere.nverts(), 2 + 7 * 16);
+        assert_eq!(sphere.nfaces(), 2 * 16 * 7);
+        assert!(faces_outwards(&sphere));
+    }
+
+    #[test]
+    fn test_face_normal() {
+        // A single counter-clockwise triangle in the xy plane.
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        assert!(model
+            .face_normal(0)
+            .approx_eq(&Vec3f::new(0.0, 0.0, 1.0), 1e-6));
+    }
+
+    #[test]
+    fn test_materials() {
+        let model = Model::new("tests/models/textured.obj").expect("Failed to load model.");
+        assert_eq!(model.materials().len(), 2);
+        assert_eq!(
+            model.diffuse_map(),
+            Some(Path::new("tests/models/textured_diffuse.tga"))
+        );
+
+        let model = Model::new("tests/models/missing_mtl.obj")
+            .expect("Missing material library should not prevent loading.");
+        assert!(model.materials().is_empty());
+        assert_eq!(
+            model.missing_material_libs(),
+            [PathBuf::from("tests/models/does_not_exist.mtl")]
+        );
+        assert_eq!(model.nfaces(), 1);
+    }
+
+    #[test]
+    fn test_face_materials() {
+        let model = Model::new("tests/models/two_materials.obj").expect("Failed to load model.");
+        let names: Vec<_> = (0..model.nfaces())
+            .map(|i| &model.materials()[model.face_material(i).unwrap()].name)
+            .collect();
+        assert_eq!(names, ["blue", "red"]);
+        assert_eq!(model.materials()[1].diffuse_color, [0.5; 3]);
+        assert_eq!(model.materials()[1].specular_color, [0.2; 3]);
+        assert_eq!(model.materials()[0].specular_color, [0.0; 3]);
+
+        // Without `usemtl` faces have no material.
+        let model = Model::new("tests/models/missing_texture.obj").expect("Failed to load model.");
+        assert_eq!(model.face_material(0), None);
+    }
+
+    #[test]
+    fn test_smoothing_groups() {
+        // Two faces share the edge between vertices 2 and 3 but sit in different groups.
+        let mut model = Model::new("tests/models/smoothing.obj").expect("Failed to load model.");
+        assert_eq!(model.smoothing_groups, vec![Some(1), Some(2)]);
+
+        model.compute_vertex_normals(180.0);
+        let n0 = model.normal(model.face(0)[1][2]);
+        let n1 = model.normal(model.face(1)[0][2]);
+        assert!(!n0.approx_eq(&n1, 1e-3), "{} == {}", n0, n1);
+        assert!(n0.approx_eq(&model.face_normal(0), 1e-6));
+        assert!(n1.approx_eq(&model.face_normal(1), 1e-6));
+
+        // Without the group split the shared vertex is smoothed into one normal.
+        model.smoothing_groups = vec![Some(1), Some(1)];
+        model.compute_vertex_normals(180.0);
+        assert_eq!(model.face(0)[1][2], model.face(1)[0][2]);
+    }
+
+    #[test]
+    fn test_lines_and_param_verts() {
+        let model = Model::new("tests/models/polyline.obj").expect("Failed to load model.");
+        assert_eq!(model.lines(), [vec![0, 1, 2], vec![2, 0]]);
+        assert_eq!(model.nfaces(), 0);
+        assert!(model.param_verts()[0].approx_eq(&Vec3f::new(0.5, 0.0, 1.0), 0.0));
+        assert!(model.param_verts()[1].approx_eq(&Vec3f::new(0.1, 0.2, 0.3), 0.0));
+    }
+
+    #[test]
+    fn test_compute_vertex_normals() {
+        let mut model = Model::new("tests/models/cube.obj").expect("Failed to load model.");
+
+        // Cube faces meet at 90 degrees, so each corner splits into three axis-aligned normals.
+        model.compute_vertex_normals(30.0);
+        assert_eq!(model.normals.len(), 24);
+        for face in model.faces_iter() {
+            for idxs in face {
+                let n = model.normal(idxs[2]);
+                let axis_aligned = [n.x, n.y, n.z]
+                    .iter()
+                    .filter(|c| (c.abs() - 1.0).abs() < 1e-6)
+                    .count();
+                assert_eq!(axis_aligned, 1, "normal: {}", n);
+            }
+        }
+
+        // Above 90 degrees every corner is smoothed into a single diagonal normal.
+        model.compute_vertex_normals(120.0);
+        assert_eq!(model.normals.len(), 8);
+        let n = model.normal(model.face(0)[0][2]);
+        let diagonal = 1.0 / 3.0_f32.sqrt();
+        assert!((n.x.abs() - diagonal).abs() < 1e-6, "normal: {}", n);
+    }
+
+    #[test]
+    fn test_slices() {
+        let model = Model::new("tests/models/sample.obj").expect("Failed to load model.");
+        assert_eq!(model.verts().len(), model.nverts());
+        assert_eq!(model.uvs().len(), model.uvs.len());
+        assert_eq!(model.normals().len(), model.normals.len());
+        assert_eq!(model.faces_iter().count(), model.nfaces());
+    }
+
+    #[test]
+    fn test_save_obj() {
+        let model = Model::new("tests/models/sample.obj").expect("Failed to load model.");
+        let path = std::env::temp_dir().join("tinyrenderer_test_save_obj.obj");
+        let path = path.to_str().unwrap();
+        model.save_obj(path).unwrap();
+        let reloaded = Model::new(path).expect("Failed to reload model.");
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(reloaded.nverts(), model.nverts());
+        assert_eq!(reloaded.uvs().len(), model.uvs().len());
+        assert_eq!(reloaded.normals().len(), model.normals().len());
+        for (a, b) in model.verts().iter().zip(reloaded.verts()) {
+            assert!(a.approx_eq(b, 0.0));
+        }
+        for (a, b) in model.uvs().iter().zip(reloaded.uvs()) {
+            assert!(a.approx_eq(b, 0.0));
+        }
+        for (a, b) in model.normals().iter().zip(reloaded.normals()) {
+            assert!(a.approx_eq(b, 0.0));
+        }
+        assert_eq!(reloaded.corners, model.corners);
+        assert_eq!(reloaded.faces, model.faces);
+        assert_eq!(reloaded.groups(), model.groups());
+    }
+
+    #[test]
+    fn test_whitespace_and_comments() {
+        let model = Model::new("tests/models/crlf.obj").expect("Failed to load model.");
+
+        assert_eq!(model.nverts(), 3);
+        assert!(model.vert(0).approx_eq(&Vec3f::new(0.0, 0.0, 0.0), 0.0));
+        // Indented with spaces and separated by tabs, followed by an inline comment.
+        assert!(model.vert(1).approx_eq(&Vec3f::new(1.0, 0.0, 0.0), 0.0));
+        assert!(model.uv(0).approx_eq(&Vec2f::new(0.5, 0.5), 0.0));
         assert_eq!(
-            model.faces[0],
-            vec![vec![0, 0, 0], vec![1, 1, 0], vec![2, 2, 0]]
+            model.faces_iter().collect::<Vec<_>>(),
+            [[[0, 0, 0], [1, 0, 0], [2, 0, 0]]]
         );
+        assert_eq!(model.groups(), &[("tri".to_string(), 0..1)]);
     }
 }
@@ -1,21 +1,426 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
 use crate::{
-    geometry::{Matrix, Vec2f, Vec2i, Vec3f, Vec3i},
-    tgaimage::{Format, TGAColor, TGAImage},
+    geometry::{Matrix, Vec2f, Vec2i, Vec3f},
+    model::Winding,
+    tgaimage::{clamp_u8, linear_to_srgb, srgb_to_linear, Format, HdrImage, TGAColor, TGAImage},
 };
 
+/// Triangles covering less screen area than this, in square pixels, are not rasterized.
+const MIN_TRIANGLE_AREA: f32 = 0.5;
+
+/// Sobel magnitude of the normal buffer above which `render_model_toon` draws an outline.
+const TOON_OUTLINE_THRESHOLD: u8 = 64;
+
+/// Largest depth difference a prepass fragment may have from the stored depth and still count
+/// as the visible one.
+const DEPTH_EQUAL_EPSILON: f32 = 1e-3;
+
+/// Depth handling of one pass of `draw_triangle_shaded`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DepthPass {
+    /// Usual less-than depth test, writing depth and color
+    Standard,
+    /// Depth test writing depth only, without shading
+    DepthOnly,
+    /// Shades only fragments at the depth laid down by a `DepthOnly` pass
+    Equal,
+}
+
+/// Depth of the scene as seen from a directional light, for shadow lookups.
+struct ShadowMap {
+    /// Light space axes: x and y span the map and z points toward the light
+    axes: [Vec3f; 3],
+    /// Light space xy of the map's corner
+    origin: Vec2f,
+    texel_size: f32,
+    size: usize,
+    /// Light space depth of the nearest surface per texel, indexed `[x][y]`
+    depth: Vec<Vec<f32>>,
+}
+
+impl ShadowMap {
+    /// Renders `triangles`, given in world space, into a `size`x`size` map fitted around them
+    /// as seen from a light shining along `light`.
+    fn new(triangles: &[[Vec3f; 3]], light: Vec3f, size: usize) -> Self {
+        let z = (light * -1.0).normalize(1.0);
+        let up = if z.y.abs() > 0.99 {
+            Vec3f::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3f::new(0.0, 1.0, 0.0)
+        };
+        let x = up.cross(z).normalize(1.0);
+        let mut map = ShadowMap {
+            axes: [x, z.cross(x), z],
+            origin: Vec2f::new(0.0, 0.0),
+            texel_size: 1.0,
+            size,
+            depth: vec![vec![f32::NEG_INFINITY; size]; size],
+        };
+
+        let triangles: Vec<[Vec3f; 3]> = triangles
+            .iter()
+            .map(|t| t.map(|p| map.to_light(p)))
+            .collect();
+        let mut min = Vec2f::new(f32::INFINITY, f32::INFINITY);
+        let mut max = Vec2f::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for p in triangles.iter().flatten() {
+            min = Vec2f::new(min.x.min(p.x), min.y.min(p.y));
+            max = Vec2f::new(max.x.max(p.x), max.y.max(p.y));
+        }
+        let extent = (max.x - min.x).max(max.y - min.y);
+        if extent > 0.0 {
+            map.origin = min;
+            map.texel_size = extent / size as f32;
+        }
+
+        for t in triangles {
+            let [t0, t1, t2] = t.map(|p| {
+                Vec3f::new(
+                    (p.x - map.origin.x) / map.texel_size,
+                    (p.y - map.origin.y) / map.texel_size,
+                    p.z,
+                )
+            });
+            let area = (t1.x - t0.x) * (t2.y - t0.y) - (t2.x - t0.x) * (t1.y - t0.y);
+            if area == 0.0 {
+                continue;
+            }
+            let x_min = t0.x.min(t1.x).min(t2.x).floor().max(0.0) as usize;
+            let x_max = (t0.x.max(t1.x).max(t2.x).ceil() as usize).min(size);
+            let y_min = t0.y.min(t1.y).min(t2.y).floor().max(0.0) as usize;
+            let y_max = (t0.y.max(t1.y).max(t2.y).ceil() as usize).min(size);
+            for x in x_min..x_max {
+                for y in y_min..y_max {
+                    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let edge =
+                        |a: Vec3f, b: Vec3f| (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x);
+                    let [w0, w1, w2] = [edge(t1, t2), edge(t2, t0), edge(t0, t1)].map(|e| e / area);
+                    if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                        continue;
+                    }
+                    let depth = &mut map.depth[x][y];
+                    *depth = depth.max(t0.z * w0 + t1.z * w1 + t2.z * w2);
+                }
+            }
+        }
+
+        map
+    }
+
+    fn to_light(&self, p: Vec3f) -> Vec3f {
+        let [x, y, z] = self.axes;
+        Vec3f::new(p.dot(x), p.dot(y), p.dot(z))
+    }
+
+    /// Fraction of the `kernel`x`kernel` texels around world point `p` from which the light
+    /// reaches it. Even kernel sizes are rounded up and 0 or 1 take a single sample.
+    fn lit_fraction(&self, p: Vec3f, kernel: u32) -> f32 {
+        // Keeps surfaces from shadowing themselves through depth and position rounding
+        let bias = 3.0 * self.texel_size;
+        let l = self.to_light(p);
+        let cx = ((l.x - self.origin.x) / self.texel_size).floor() as i64;
+        let cy = ((l.y - self.origin.y) / self.texel_size).floor() as i64;
+        let radius = kernel as i64 / 2;
+
+        let mut lit = 0;
+        for x in cx - radius..=cx + radius {
+            for y in cy - radius..=cy + radius {
+                // Nothing outside the map casts a shadow
+                let inside =
+                    (0..self.size as i64).contains(&x) && (0..self.size as i64).contains(&y);
+                if !inside || l.z + bias >= self.depth[x as usize][y as usize] {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / ((2 * radius + 1) * (2 * radius + 1)) as f32
+    }
+}
+
 pub struct Renderer {
     width: i32,
     height: i32,
     image: TGAImage,
+    depth_scale: f32,
+    /// Current model matrix applied by `render_model`, with the matrices saved by `push_matrix`
+    model_matrix: Matrix,
+    matrix_stack: Vec<Matrix>,
+    view: Matrix,
+    projection: Matrix,
+    viewport: Matrix,
+    /// Whether `set_viewport` replaced the default viewport, which the screen-space renderers
+    /// then honour instead of covering the whole image
+    viewport_set: bool,
+    texture_filter: TextureFilter,
+    texture: Option<TGAImage>,
+    uv_scale: Vec2f,
+    uv_offset: Vec2f,
+    flip_texture_v: bool,
+    missing_texel: MissingTexel,
+    wrap_mode: WrapMode,
+    /// Face drawn at each pixel, -1 for none, indexed by `x + y * width` like the z-buffer
+    id_buffer: Option<Vec<i32>>,
+    edge_antialiasing: bool,
+    rasterizer: Rasterizer,
+    /// Pixels triangles may cover, indexed by `x + y * width`
+    mask: Option<Vec<bool>>,
+    /// Region `render_model_tiled` is currently filling
+    tile: Option<Rect>,
+    double_sided: bool,
+    texture_srgb: bool,
+    shading: ShadingMode,
+    cull_mode: CullMode,
+    front_face: Winding,
+    ambient: f32,
+    /// Normalized directions the lights shine in
+    lights: Vec<Vec3f>,
+    gamma: f32,
+    depth_prepass: bool,
+    reverse_z: bool,
+    /// Depth z-buffers start at, `None` for the far end of the depth range
+    clear_depth: Option<f32>,
+    shadows: bool,
+    shadow_pcf: u32,
+    /// `(factor, units)` of `set_polygon_offset`
+    polygon_offset: (f32, f32),
+    /// Texture alpha below which textured fragments are discarded
+    alpha_cutoff: Option<f32>,
+    /// Shadow map of the render in progress
+    shadow_map: Option<ShadowMap>,
+    /// Unclamped linear RGB written alongside `image` by the textured rasterizers
+    hdr: Option<HdrImage>,
+    /// Counters of the render in progress
+    stats: RenderStats,
+    /// Z-buffer of the last render
+    depth_buffer: Option<Vec<f32>>,
 }
 
+/// Line drawing algorithm used by `draw_line`. Level0 and Level1 use floating point and may
+/// differ by a pixel across platforms; Level2 and Level3 are integer-only and exact.
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum OptimizationLevel {
     Level0,
     Level1,
+    #[default]
     Level2,
+    /// Level2 writing straight into the pixel buffer, falling back to Level2 for lines that
+    /// leave the image.
+    Level3,
+}
+
+/// How `render_model` samples the diffuse texture.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    Trilinear,
+}
+
+/// How `render_model` computes light intensity across a face.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadingMode {
+    /// One intensity per face from its geometric normal
+    Flat,
+    /// Intensities computed at the vertices from the model's normals and interpolated
+    Gouraud,
+}
+
+/// How `render_model` fills textured triangles when edge antialiasing is off.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rasterizer {
+    /// Sorts the vertices by y and walks the scanlines between the edges
+    #[default]
+    Scanline,
+    /// Visits the pixel centers in the screen bounding box and keeps those whose barycentric
+    /// weights are all non-negative
+    Barycentric,
+}
+
+/// Which faces `render_model` skips by their screen-space winding, judged against the front
+/// face winding set with `set_front_face`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CullMode {
+    /// Draws every face. Back faces are lit by their own normal, so they usually come out at
+    /// the ambient level unless the renderer is double-sided.
+    None,
+    #[default]
+    Back,
+    Front,
+}
+
+/// How texture sampling maps uvs outside [0, 1] back onto the texture.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WrapMode {
+    /// Uses the nearest edge texel
+    #[default]
+    Clamp,
+    /// Tiles the texture, keeping the fractional part of the uv
+    Repeat,
+}
+
+/// What textured rasterization does with uvs outside [0, 1] or texels that cannot be sampled.
+#[derive(Debug, Clone, Copy)]
+enum MissingTexel {
+    /// Samples as usual by the wrap mode, failing with `RenderError::TextureOutOfBounds` where
+    /// nothing can be sampled, as with an empty texture
+    Error,
+    Fill(TGAColor),
+    Clamp,
+}
+
+/// Projection used by `render_model_with_camera`. `fov` is the vertical field of view in
+/// radians and `size` is half the height of the orthographic view volume.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum CameraMode {
+    Perspective { fov: f32, near: f32, far: f32 },
+    Orthographic { size: f32, near: f32, far: f32 },
+}
+
+/// Camera keyframes as `(eye, center)` pairs, interpolated by a Catmull-Rom spline through
+/// every keyframe. The path is parametrized over [0, 1] with the keyframes evenly spaced.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    keyframes: Vec<(Vec3f, Vec3f)>,
+}
+
+#[allow(dead_code)]
+impl CameraPath {
+    /// # Panics
+    ///
+    /// If `keyframes` is empty.
+    pub fn new(keyframes: Vec<(Vec3f, Vec3f)>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "A camera path needs at least one keyframe"
+        );
+        CameraPath { keyframes }
+    }
+
+    /// Eye and center at `t`, clamped to [0, 1]. Keyframe `i` of `n` is reached at
+    /// `t = i / (n - 1)`; the end keyframes are repeated to give the end segments a tangent.
+    pub fn sample(&self, t: f32) -> (Vec3f, Vec3f) {
+        let last = self.keyframes.len() - 1;
+        let s = t.clamp(0.0, 1.0) * last as f32;
+        let i = s.floor() as usize;
+        if i >= last {
+            return self.keyframes[last];
+        }
+        let u = s - i as f32;
+        let [k0, k1, k2, k3] =
+            [i.saturating_sub(1), i, i + 1, (i + 2).min(last)].map(|k| self.keyframes[k]);
+        let spline = |p0: Vec3f, p1: Vec3f, p2: Vec3f, p3: Vec3f| {
+            (p1 * 2.0
+                + (p2 - p0) * u
+                + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (u * u)
+                + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (u * u * u))
+                * 0.5
+        };
+        (
+            spline(k0.0, k1.0, k2.0, k3.0),
+            spline(k0.1, k1.1, k2.1, k3.1),
+        )
+    }
+}
+
+/// Work done by one `render_model` call.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RenderStats {
+    pub faces_total: usize,
+    /// Faces all of whose triangles were back-face culled
+    pub faces_culled: usize,
+    /// Faces with a triangle reaching past the image edges, which rasterization clips
+    pub faces_clipped: usize,
+    /// Triangles handed to the rasterizer
+    pub triangles_drawn: usize,
+    /// Fragments that passed the mask and depth tests and were written
+    pub pixels_shaded: usize,
+    /// Fragments discarded because something nearer was already drawn
+    pub depth_tests_failed: usize,
+}
+
+/// Axis-aligned block of pixels `x..x + width` by `y..y + height`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    #[allow(dead_code)]
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+/// Errors returned by the rendering methods.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError {
+    /// A triangle spans less than one scanline, so its edges cannot be interpolated.
+    DivisionByZero,
+    /// Texture sampling failed for the fragment at pixel `(x, y)`.
+    TextureOutOfBounds { x: i32, y: i32 },
+    /// A texture referenced by the model could not be loaded.
+    MissingTexture(PathBuf),
+    /// A material library named by the model could not be loaded.
+    MissingMaterialLibrary(PathBuf),
+    /// The viewport maps normalized device coordinates to pixels `x0..=x1` by `y0..=y1`, which
+    /// reach outside the `width`x`height` framebuffer.
+    ViewportOutOfBounds {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        width: i32,
+        height: i32,
+    },
 }
 
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::DivisionByZero => write!(f, "Triangle spans less than one scanline"),
+            RenderError::TextureOutOfBounds { x, y } => {
+                write!(f, "Texture lookup out of bounds at pixel ({}, {})", x, y)
+            }
+            RenderError::MissingTexture(path) => {
+                write!(f, "Failed to load texture {}", path.display())
+            }
+            RenderError::MissingMaterialLibrary(path) => {
+                write!(f, "Failed to load material library {}", path.display())
+            }
+            RenderError::ViewportOutOfBounds {
+                x0,
+                y0,
+                x1,
+                y1,
+                width,
+                height,
+            } => write!(
+                f,
+                "Viewport spans pixels ({}, {}) to ({}, {}), outside the {}x{} framebuffer",
+                x0, y0, x1, y1, width, height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
 impl Renderer {
     pub fn new(width: i32, height: i32) -> Self {
         let image = TGAImage::new(width, height, Format::RGB);
@@ -23,459 +428,4510 @@ impl Renderer {
             width,
             height,
             image,
+            depth_scale: 1000.0,
+            model_matrix: Matrix::identity(4),
+            matrix_stack: Vec::new(),
+            view: Self::lookat(
+                Vec3f::new(3.0, 1.0, 3.0),
+                Vec3f::new(0.0, 0.0, 0.0),
+                Vec3f::new(0.0, 1.0, 0.0),
+            ),
+            projection: Matrix::identity(4),
+            viewport: Self::viewport_from_rect(
+                width / 8,
+                height / 8,
+                width * 3 / 4,
+                height * 3 / 4,
+            ),
+            viewport_set: false,
+            texture_filter: TextureFilter::Nearest,
+            texture: None,
+            uv_scale: Vec2f::new(1.0, 1.0),
+            uv_offset: Vec2f::new(0.0, 0.0),
+            flip_texture_v: false,
+            missing_texel: MissingTexel::Error,
+            wrap_mode: WrapMode::Clamp,
+            id_buffer: None,
+            edge_antialiasing: false,
+            rasterizer: Rasterizer::Scanline,
+            mask: None,
+            tile: None,
+            double_sided: false,
+            texture_srgb: false,
+            shading: ShadingMode::Flat,
+            cull_mode: CullMode::Back,
+            front_face: Winding::CounterClockwise,
+            ambient: 0.0,
+            lights: vec![Vec3f::new(0.0, 0.0, -1.0)],
+            gamma: 1.0,
+            depth_prepass: false,
+            reverse_z: false,
+            clear_depth: None,
+            shadows: false,
+            shadow_pcf: 1,
+            polygon_offset: (0.0, 0.0),
+            alpha_cutoff: None,
+            shadow_map: None,
+            hdr: None,
+            stats: RenderStats::default(),
+            depth_buffer: None,
         }
     }
 
-    pub fn save_tga_image(&mut self, filename: &str) -> std::io::Result<()> {
-        self.image.flip_vertically();
-        self.image.write_tga_file(filename, true)
+    /// Starts configuring a renderer with chainable setters.
+    #[allow(dead_code)]
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder::new()
     }
 
+    /// Blends partially covered triangle edge pixels in `render_model` by their coverage.
     #[allow(dead_code)]
-    pub fn render_model(
-        &mut self,
-        model: &crate::model::Model,
-        texture_image: &TGAImage,
-    ) -> Result<(), String> {
-        let light_dir = Vec3f::new(0.0, 0.0, -1.0);
-        let mut zbuffer = vec![
-            vec![i32::min_value(); self.image.width as usize + 1];
-            self.image.height as usize + 1
-        ];
+    pub fn set_edge_antialiasing(&mut self, enabled: bool) {
+        self.edge_antialiasing = enabled;
+    }
 
-        for i in 0..model.nfaces() {
-            let face = model.face(i);
-            let mut screen_coords = vec![Vec3i::new(0, 0, 0); 3];
-            let mut world_coords = vec![Vec3f::new(0.0, 0.0, 0.0); 3];
-            let mut texture_coords = vec![Vec2f::new(0.0, 0.0); 3];
-            for j in 0..3 {
-                let v = model.vert(face[j][0]);
-                screen_coords[j] = Vec3i::new(
-                    ((v.x + 1.0) * self.width as f32 / 2.0) as i32,
-                    ((v.y + 1.0) * self.height as f32 / 2.0) as i32,
-                    (v.z * 1000.0) as i32,
-                );
-                world_coords[j] = v;
-                texture_coords[j] = model.uv(face[j][1]);
-            }
-            let mut n =
-                (world_coords[2] - world_coords[0]).cross(world_coords[1] - world_coords[0]);
-            n = n.normalize(1.0);
-            let intensity = n.dot(light_dir);
-            if intensity > 0.0 {
-                self.draw_triangle(
-                    screen_coords[0],
-                    screen_coords[1],
-                    screen_coords[2],
-                    texture_coords[0],
-                    texture_coords[1],
-                    texture_coords[2],
-                    texture_image,
-                    intensity,
-                    &mut zbuffer,
-                )?;
-            }
-        }
+    /// Chooses between one intensity per face and intensities lit at the vertices from the
+    /// model's `vn` normals and interpolated across the face. Faces without normals are shaded
+    /// flat either way.
+    #[allow(dead_code)]
+    pub fn set_shading(&mut self, shading: ShadingMode) {
+        self.shading = shading;
+    }
 
-        Ok(())
+    /// Replaces the lights with a single directional light shining along `dir`, which need not
+    /// be normalized. The default light shines along -z, into the screen.
+    #[allow(dead_code)]
+    pub fn set_light_dir(&mut self, dir: Vec3f) {
+        self.lights = vec![dir.normalize(1.0)];
     }
 
+    /// Chooses how `render_model` fills triangles without edge antialiasing.
     #[allow(dead_code)]
-    pub fn render_model_with_camera(&mut self, model: &crate::model::Model) -> Result<(), String> {
-        let light_dir = Vec3f::new(1.0, -1.0, 1.0).normalize(1.0);
-        let eye = Vec3f::new(3.0, 1.0, 3.0);
-        let center = Vec3f::new(0.0, 0.0, 0.0);
-        let model_view = Self::lookat(eye, center, Vec3f::new(0.0, 1.0, 0.0));
-        let camera = Vec3f::new(0.0, 0.0, 3.0);
-        let viewport = Self::viewport(
-            self.width / 8,
-            self.height / 8,
-            self.width * 3 / 4,
-            self.height * 3 / 4,
-        );
-        let projection = Matrix::projection(camera.z);
+    pub fn set_rasterizer(&mut self, rasterizer: Rasterizer) {
+        self.rasterizer = rasterizer;
+    }
 
-        let mut zbuffer = vec![
-            vec![i32::min_value(); self.image.width as usize + 1];
-            self.image.height as usize + 1
-        ];
+    /// Makes `render_model_shaded` lay down depth for the whole model before shading, so the
+    /// fragment function only runs for visible fragments.
+    #[allow(dead_code)]
+    pub fn enable_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
 
-        for i in 0..model.nfaces() {
-            let face = model.face(i);
-            let mut screen_coords = vec![Vec3i::new(0, 0, 0); 3];
-            let mut world_coords = vec![Vec3f::new(0.0, 0.0, 0.0); 3];
-            let mut normals = vec![0.0 as f32; 3];
-            for j in 0..3 {
-                let v = model.vert(face[j][0]);
-                screen_coords[j] =
-                    (viewport.clone() * projection.clone() * model_view.clone() * v.to_mat())
-                        .to_vec()
-                        .to_i();
-                normals[j] = model.normal(face[j][2]).normalize(1.0).dot(light_dir);
-                world_coords[j] = v;
-            }
-            self.draw_triangle_with_normal(
-                screen_coords[0],
-                screen_coords[1],
-                screen_coords[2],
-                normals[0],
-                normals[1],
-                normals[2],
-                &mut zbuffer,
-            )?;
-        }
+    /// Casts shadows from the first light in `render_model` and `render_scene`, using a shadow
+    /// map rendered along the light's direction. Fragments the light cannot reach keep only the
+    /// ambient light.
+    #[allow(dead_code)]
+    pub fn set_shadows(&mut self, enabled: bool) {
+        self.shadows = enabled;
+    }
 
-        Ok(())
+    /// Sets the size of the square of shadow map texels averaged per fragment, softening shadow
+    /// edges with percentage-closer filtering. 1 gives hard shadows.
+    #[allow(dead_code)]
+    pub fn set_shadow_pcf(&mut self, kernel: u32) {
+        self.shadow_pcf = kernel;
     }
 
-    fn lookat(eye: Vec3f, center: Vec3f, up: Vec3f) -> Matrix {
-        let z = (eye - center).normalize(1.0);
-        let x = (up.cross(z)).normalize(1.0);
-        let y = (z.cross(x)).normalize(1.0);
+    /// Lights faces seen from behind as if they faced the viewer, for thin surfaces like leaves
+    /// or cloth. Back faces are then drawn instead of culled.
+    #[allow(dead_code)]
+    pub fn set_double_sided(&mut self, enabled: bool) {
+        self.double_sided = enabled;
+    }
 
-        Matrix::from_vec(vec![
-            vec![x.x, x.y, x.z, -center.x],
-            vec![y.x, y.y, y.z, -center.y],
-            vec![z.x, z.y, z.z, -center.z],
-            vec![0.0, 0.0, 0.0, 1.0],
-        ])
+    /// Treats diffuse textures as sRGB: texels are decoded to linear before lighting and the lit
+    /// color is encoded back to sRGB when written.
+    #[allow(dead_code)]
+    pub fn set_texture_srgb(&mut self, enabled: bool) {
+        self.texture_srgb = enabled;
     }
 
-    fn viewport(x: i32, y: i32, w: i32, h: i32) -> Matrix {
-        let depth = 255;
+    /// Restricts triangle rasterization to pixels where `mask` is true. The mask has one entry
+    /// per pixel, indexed by `x + y * width` like the framebuffer.
+    #[allow(dead_code)]
+    pub fn set_mask(&mut self, mask: Vec<bool>) {
+        assert_eq!(
+            mask.len(),
+            (self.image.width * self.image.height) as usize,
+            "Mask must have one entry per pixel"
+        );
+        self.mask = Some(mask);
+    }
 
-        let mut m = Matrix::identity(4);
-        m[0][3] = (x + w) as f32 / 2.0;
-        m[1][3] = (y + h) as f32 / 2.0;
-        m[2][3] = depth as f32 / 2.0;
+    /// Lets triangles cover the whole framebuffer again.
+    #[allow(dead_code)]
+    pub fn clear_mask(&mut self) {
+        self.mask = None;
+    }
 
-        m[0][0] = w as f32 / 2.0;
-        m[1][1] = h as f32 / 2.0;
-        m[2][2] = depth as f32 / 2.0;
+    fn mask_allows(&self, x: i32, y: i32) -> bool {
+        if let Some(tile) = self.tile {
+            if !tile.contains(x, y) {
+                return false;
+            }
+        }
+        match &self.mask {
+            Some(mask) => {
+                x >= 0
+                    && y >= 0
+                    && x < self.image.width
+                    && y < self.image.height
+                    && mask[(x + y * self.image.width) as usize]
+            }
+            None => true,
+        }
+    }
 
-        m
+    /// Chooses which faces `render_model` culls. Back faces by default; double-sided rendering
+    /// keeps them regardless.
+    #[allow(dead_code)]
+    pub fn set_cull_mode(&mut self, mode: CullMode) {
+        self.cull_mode = mode;
     }
 
-    fn draw_triangle_with_normal(
-        &mut self,
-        mut t0: Vec3i,
-        mut t1: Vec3i,
-        mut t2: Vec3i,
-        mut ity0: f32,
-        mut ity1: f32,
-        mut ity2: f32,
-        zbuffer: &mut Vec<Vec<i32>>,
-    ) -> Result<(), String> {
-        if t0.y == t1.y && t0.y == t2.y {
-            // Degenerate triangle
-            return Ok(());
+    /// Sets the screen-space winding of front faces. With back-face culling on, triangles that
+    /// wind the other way after projection are skipped. Counter-clockwise by default.
+    #[allow(dead_code)]
+    pub fn set_front_face(&mut self, winding: Winding) {
+        self.front_face = winding;
+    }
+
+    /// Enables or disables recording which face covers each pixel, for use with `pick`.
+    #[allow(dead_code)]
+    pub fn set_picking(&mut self, enabled: bool) {
+        self.id_buffer = None;
+        if enabled {
+            self.id_buffer = Some(Vec::new());
+            self.reset_id_buffer();
         }
+    }
 
-        let image = &mut self.image;
+    /// Index of the face visible at pixel (x, y) in the last render, if picking is enabled.
+    #[allow(dead_code)]
+    pub fn pick(&self, x: i32, y: i32) -> Option<usize> {
+        let k = self.depth_index(x, y)?;
+        let id = *self.id_buffer.as_ref()?.get(k)?;
+        usize::try_from(id).ok()
+    }
 
-        // Sort the vertices by y-coordinate ascending (t0.y <= t1.y <= t2.y)
-        if t0.y > t1.y {
-            std::mem::swap(&mut t0, &mut t1);
-            std::mem::swap(&mut ity0, &mut ity1);
-        }
-        if t0.y > t2.y {
-            std::mem::swap(&mut t0, &mut t2);
-            std::mem::swap(&mut ity0, &mut ity2);
-        }
-        if t1.y > t2.y {
-            std::mem::swap(&mut t1, &mut t2);
-            std::mem::swap(&mut ity1, &mut ity2);
+    fn reset_id_buffer(&mut self) {
+        if let Some(ids) = self.id_buffer.as_mut() {
+            *ids = vec![-1; (self.image.width * self.image.height) as usize];
         }
+    }
 
-        let total_height = t2.y - t0.y;
-        if total_height == 0 {
-            return Err("DivisionByZero".to_string());
-        }
+    #[allow(dead_code)]
+    pub fn set_texture_filter(&mut self, filter: TextureFilter) {
+        self.texture_filter = filter;
+    }
 
-        for i in 0..total_height {
-            let second_half = i > t1.y - t0.y || t1.y == t0.y;
-            let segment_height = if second_half {
-                t2.y - t1.y
-            } else {
-                t1.y - t0.y
-            };
-            let alpha = i as f32 / total_height as f32;
-            let beta =
-                (i - if second_half { t1.y - t0.y } else { 0 }) as f32 / segment_height as f32;
-            let mut p_a = t0.to_f() + (t2.to_f() - t0.to_f()) * alpha;
-            let mut p_b = if second_half {
-                t1.to_f() + (t2.to_f() - t1.to_f()) * beta
-            } else {
-                t0.to_f() + (t1.to_f() - t0.to_f()) * beta
-            };
-            let mut ity_a = ity0 + (ity2 - ity0) * alpha;
-            let mut ity_b = if second_half {
-                ity1 + (ity2 - ity1) * beta
-            } else {
-                ity0 + (ity1 - ity0) * beta
-            };
+    /// Chooses how uvs outside [0, 1], including those interpolation overshoots by a hair, are
+    /// sampled. Clamping by default.
+    #[allow(dead_code)]
+    pub fn set_wrap_mode(&mut self, mode: WrapMode) {
+        self.wrap_mode = mode;
+    }
 
-            if p_a.x > p_b.x {
-                std::mem::swap(&mut p_a, &mut p_b);
-                std::mem::swap(&mut ity_a, &mut ity_b);
-            }
+    /// Stores the diffuse texture used by `render_model_with_stored_texture`. With `None`, faces
+    /// are shaded in flat white.
+    #[allow(dead_code)]
+    pub fn set_texture(&mut self, texture: Option<TGAImage>) {
+        self.texture = texture;
+    }
 
-            for j in (p_a.x as i32)..=(p_b.x as i32) {
-                let phi = if p_b.x as i32 == p_a.x as i32 {
-                    1.0
-                } else {
-                    (j as f32 - p_a.x) / (p_b.x - p_a.x)
-                };
-                let p_cur = p_a + (p_b - p_a) * phi;
-                let ity_cur = ity_a + (ity_b - ity_a) * phi;
+    /// Maps model uvs into a sub-rectangle of the texture as `uv * scale + offset`, so one cell
+    /// of an atlas can be rendered without slicing the image.
+    #[allow(dead_code)]
+    pub fn set_uv_transform(&mut self, scale: Vec2f, offset: Vec2f) {
+        self.uv_scale = scale;
+        self.uv_offset = offset;
+    }
 
-                if zbuffer[p_cur.x as usize][p_cur.y as usize] < p_cur.z as i32 {
-                    zbuffer[p_cur.x as usize][p_cur.y as usize] = p_cur.z as i32;
-                    image.set(
-                        p_cur.x as i32,
-                        p_cur.y as i32,
-                        &TGAColor::rgba(
-                            (255.0 * ity_cur) as u8,
-                            (255.0 * ity_cur) as u8,
-                            (255.0 * ity_cur) as u8,
-                            255,
-                        ),
-                    );
-                }
+    /// Handles fragments whose uv falls outside the texture without aborting the render:
+    /// `Some(color)` shades them as if `color` had been sampled, which makes broken uvs easy to
+    /// spot, and `None` clamps the uv onto the nearest edge texel.
+    #[allow(dead_code)]
+    pub fn set_missing_texel_color(&mut self, color: Option<TGAColor>) {
+        self.missing_texel = match color {
+            Some(c) => MissingTexel::Fill(c),
+            None => MissingTexel::Clamp,
+        };
+    }
+
+    /// Samples model uvs at `1 - v`, for assets whose uv origin is at the other vertical end of
+    /// the texture. Applied before the uv transform.
+    #[allow(dead_code)]
+    pub fn set_flip_texture_v(&mut self, enabled: bool) {
+        self.flip_texture_v = enabled;
+    }
+
+    /// Saves the current model matrix so a later `pop_matrix` can restore it.
+    #[allow(dead_code)]
+    pub fn push_matrix(&mut self) {
+        self.matrix_stack.push(self.model_matrix.clone());
+    }
+
+    /// Restores the model matrix saved by the matching `push_matrix`. Returns false, leaving the
+    /// matrix unchanged, if nothing was pushed.
+    #[allow(dead_code)]
+    pub fn pop_matrix(&mut self) -> bool {
+        match self.matrix_stack.pop() {
+            Some(m) => {
+                self.model_matrix = m;
+                true
             }
+            None => false,
         }
+    }
 
-        Ok(())
+    /// Multiplies the current model matrix by `m` on the right, so `m` applies to the model
+    /// before the transforms already on the matrix.
+    #[allow(dead_code)]
+    pub fn mul_matrix(&mut self, m: Matrix) {
+        self.model_matrix = self.model_matrix.clone() * m;
     }
 
-    fn draw_triangle(
-        &mut self,
-        mut t0: Vec3i,
-        mut t1: Vec3i,
-        mut t2: Vec3i,
-        mut uv0: Vec2f,
-        mut uv1: Vec2f,
-        mut uv2: Vec2f,
-        texture_image: &TGAImage,
-        intensity: f32,
-        zbuffer: &mut Vec<Vec<i32>>,
-    ) -> Result<(), String> {
-        if t0.y == t1.y && t0.y == t2.y {
-            // Degenerate triangle
-            return Ok(());
-        }
+    /// Model matrix `render_model` currently applies.
+    #[allow(dead_code)]
+    pub fn model_matrix(&self) -> &Matrix {
+        &self.model_matrix
+    }
 
-        let image = &mut self.image;
+    /// Positions the camera used by `render_model_with_camera`, replacing the view matrix.
+    #[allow(dead_code)]
+    pub fn set_camera(&mut self, eye: Vec3f, center: Vec3f, up: Vec3f) {
+        self.view = Self::lookat(eye, center, up);
+    }
 
-        // Sort the vertices by y-coordinate ascending (t0.y <= t1.y <= t2.y)
-        if t0.y > t1.y {
-            std::mem::swap(&mut t0, &mut t1);
-            std::mem::swap(&mut uv0, &mut uv1);
-        }
-        if t0.y > t2.y {
-            std::mem::swap(&mut t0, &mut t2);
-            std::mem::swap(&mut uv0, &mut uv2);
-        }
-        if t1.y > t2.y {
-            std::mem::swap(&mut t1, &mut t2);
-            std::mem::swap(&mut uv1, &mut uv2);
+    #[allow(dead_code)]
+    pub fn set_view(&mut self, view: Matrix) {
+        self.view = view;
+    }
+
+    /// Note that `render_model_with_camera` replaces the projection with the one for its
+    /// `CameraMode`.
+    #[allow(dead_code)]
+    pub fn set_projection(&mut self, projection: Matrix) {
+        self.projection = projection;
+    }
+
+    /// Sets the viewport of `render_model_with_camera`. `render_model` and the debug views map
+    /// onto its pixel rectangle too, keeping their own depth range.
+    #[allow(dead_code)]
+    pub fn set_viewport(&mut self, viewport: Matrix) {
+        self.viewport = viewport;
+        self.viewport_set = true;
+    }
+
+    #[allow(dead_code)]
+    pub fn view(&self) -> &Matrix {
+        &self.view
+    }
+
+    #[allow(dead_code)]
+    pub fn projection(&self) -> &Matrix {
+        &self.projection
+    }
+
+    #[allow(dead_code)]
+    pub fn viewport(&self) -> &Matrix {
+        &self.viewport
+    }
+
+    #[allow(dead_code)]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[allow(dead_code)]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Replaces the framebuffer with a cleared `w`x`h` one of the same format. The viewport is
+    /// reset to the default for the new size and the mask and last z-buffer, which no longer
+    /// fit, are removed.
+    #[allow(dead_code)]
+    pub fn resize(&mut self, w: i32, h: i32) {
+        let format = self.image.format().unwrap_or(Format::RGB);
+        self.width = w;
+        self.height = h;
+        self.image = TGAImage::new(w, h, format);
+        self.viewport = Self::viewport_from_rect(w / 8, h / 8, w * 3 / 4, h * 3 / 4);
+        self.viewport_set = false;
+        self.mask = None;
+        self.depth_buffer = None;
+        if self.hdr.is_some() {
+            self.hdr = Some(HdrImage::new(w, h, 3));
         }
+        self.reset_id_buffer();
+    }
 
-        let total_height = t2.y - t0.y;
-        if total_height == 0 {
-            return Err("DivisionByZero".to_string());
+    /// Projects a world-space point to the pixel it lands on through the view, projection and
+    /// viewport matrices.
+    #[allow(dead_code)]
+    pub fn world_to_screen(&self, p: Vec3f) -> Vec2i {
+        let s = (self.viewport.clone() * self.projection.clone() * self.view.clone() * p.to_mat())
+            .to_vec();
+        Vec2i::new(s.x.trunc() as i32, s.y.trunc() as i32)
+    }
+
+    /// Sets the depth the viewport maps normalized device z onto: z in -1..=1 is written to the
+    /// z-buffer within `0..=depth`, so a larger range gives finer depth resolution.
+    #[allow(dead_code)]
+    pub fn set_depth_range(&mut self, depth: f32) {
+        self.depth_scale = depth;
+    }
+
+    /// Chooses the z-buffer convention. By default depths run from 0 at the near end of the depth
+    /// range to its far end, nearer is smaller and buffers clear to the far end. Reverse-Z flips
+    /// the range: nearer is larger and buffers clear to 0. Occlusion is the same either way.
+    #[allow(dead_code)]
+    pub fn set_reverse_z(&mut self, enabled: bool) {
+        self.reverse_z = enabled;
+    }
+
+    /// Biases the depth of every triangle rasterized from now on by
+    /// `factor * slope + units`, like `glPolygonOffset`, where `slope` is the larger of the
+    /// triangle's depth changes per pixel along x and y and a unit is one z-buffer unit (see
+    /// `set_depth_range`). Positive offsets push faces away from the viewer and negative ones
+    /// pull them closer, so a decal drawn with a negative offset wins over the coplanar surface
+    /// under it. `(0.0, 0.0)` turns the offset off.
+    #[allow(dead_code)]
+    pub fn set_polygon_offset(&mut self, factor: f32, units: f32) {
+        self.polygon_offset = (factor, units);
+    }
+
+    /// Discards textured fragments whose texel alpha, in `0.0..=1.0`, is below `cutoff`, so
+    /// cutout textures such as foliage or fences leave what is behind them visible. Discarded
+    /// fragments write neither color nor depth. Only RGBA textures carry alpha; others are
+    /// always opaque. `None` turns the test off.
+    #[allow(dead_code)]
+    pub fn set_alpha_cutoff(&mut self, cutoff: Option<f32>) {
+        self.alpha_cutoff = cutoff;
+    }
+
+    /// Whether the alpha test rejects `texel` sampled from `texture_image`.
+    fn alpha_discards(&self, texture_image: &TGAImage, texel: &TGAColor) -> bool {
+        match self.alpha_cutoff {
+            Some(cutoff) => {
+                texture_image.format() == Some(Format::RGBA)
+                    && (texel.raw[3] as f32 / 255.0) < cutoff
+            }
+            None => false,
         }
+    }
 
-        for i in 0..total_height {
-            let second_half = i > t1.y - t0.y || t1.y == t0.y;
-            let segment_height = if second_half {
-                t2.y - t1.y
-            } else {
-                t1.y - t0.y
-            };
-            let alpha = i as f32 / total_height as f32;
-            let beta =
-                (i - if second_half { t1.y - t0.y } else { 0 }) as f32 / segment_height as f32;
-            let mut p_a = t0.to_f() + (t2.to_f() - t0.to_f()) * alpha;
-            let mut p_b = if second_half {
-                t1.to_f() + (t2.to_f() - t1.to_f()) * beta
-            } else {
-                t0.to_f() + (t1.to_f() - t0.to_f()) * beta
-            };
-            let mut uvp_a = uv0 + (uv2 - uv0) * alpha;
-            let mut uvp_b = if second_half {
-                uv1 + (uv2 - uv1) * beta
-            } else {
-                uv0 + (uv1 - uv0) * beta
-            };
+    /// Sets the depth z-buffers are cleared to, in the convention chosen by `set_reverse_z`.
+    /// Fragments not nearer than it are discarded, acting as a far plane. `None` restores the
+    /// far end of the depth range.
+    #[allow(dead_code)]
+    pub fn set_clear_depth(&mut self, depth: Option<f32>) {
+        self.clear_depth = depth;
+    }
 
-            if p_a.x > p_b.x {
-                std::mem::swap(&mut p_a, &mut p_b);
-                std::mem::swap(&mut uvp_a, &mut uvp_b);
+    /// Also accumulates textured fragments in a floating-point framebuffer, where light
+    /// intensities above 1 are kept instead of clamped. Enabling it starts a cleared buffer;
+    /// the 8-bit framebuffer is still written either way.
+    #[allow(dead_code)]
+    pub fn set_hdr(&mut self, enabled: bool) {
+        self.hdr = enabled.then(|| HdrImage::new(self.width, self.height, 3));
+    }
+
+    /// The floating-point framebuffer, if `set_hdr` enabled one.
+    #[allow(dead_code)]
+    pub fn hdr_image(&self) -> Option<&HdrImage> {
+        self.hdr.as_ref()
+    }
+
+    /// Writes the framebuffer as an RLE TGA file, gamma corrected if a gamma was configured.
+    /// With HDR enabled the floating-point framebuffer is Reinhard tone mapped and written
+    /// instead.
+    pub fn save_tga_image(&mut self, filename: &str) -> std::io::Result<()> {
+        let mut image = match &self.hdr {
+            Some(hdr) => hdr.tone_map_reinhard(),
+            None => self.image.clone(),
+        };
+        image.apply_gamma(self.gamma);
+        image.flip_vertically();
+        image.write_tga_file(filename, true)
+    }
+
+    /// Writes the z-buffer of the last render as a grayscale little-endian Portable Float Map,
+    /// one f32 per image pixel with the bottom row first. Values are stored depths as the
+    /// renderer compares them (see `set_depth_range` and `set_reverse_z`), and pixels nothing
+    /// was drawn on hold the clear depth, the far end of the range by default. Before any render
+    /// the whole buffer is the clear depth.
+    #[allow(dead_code)]
+    pub fn save_depth_pfm(&self, filename: &str) -> std::io::Result<()> {
+        let cleared;
+        let zbuffer = match &self.depth_buffer {
+            Some(zbuffer) => zbuffer,
+            None => {
+                cleared = self.new_zbuffer();
+                &cleared
             }
+        };
 
-            for j in (p_a.x as i32)..=(p_b.x as i32) {
-                let phi = if p_b.x as i32 == p_a.x as i32 {
-                    1.0
-                } else {
-                    (j as f32 - p_a.x) / (p_b.x - p_a.x)
+        let mut out = BufWriter::new(File::create(filename)?);
+        // A negative scale marks the data as little-endian
+        write!(out, "Pf\n{} {}\n-1.0\n", self.width, self.height)?;
+        for depth in zbuffer {
+            out.write_all(&depth.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    /// Renders `model` with `texture_image` using the configured lights. Faces assigned a
+    /// material by `usemtl` use its diffuse map and color instead. `progress`, if given, is
+    /// called on the calling thread roughly every 1% of faces with the fraction done, ending
+    /// with 1.0. Returns counts of the work done.
+    #[allow(dead_code)]
+    pub fn render_model(
+        &mut self,
+        model: &crate::model::Model,
+        texture_image: &TGAImage,
+        progress: Option<&dyn Fn(f32)>,
+    ) -> Result<RenderStats, RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        self.reset_id_buffer();
+        self.stats = RenderStats::default();
+        let model_matrix = self.model_matrix.clone();
+        self.update_shadow_map(&[(model, &model_matrix)]);
+        self.fill_model(model, texture_image, &model_matrix, &mut zbuffer, progress)?;
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(self.stats)
+    }
+
+    /// Renders `model` like `render_model`, one square tile of `tile_size` pixels at a time,
+    /// calling `on_tile` with each finished tile and the image so far for progressive previews.
+    /// Tiles go row by row from the origin and are cut short at the image edges. The z-buffer
+    /// spans the whole image, so the result matches `render_model`, but every tile walks all
+    /// faces of the model.
+    #[allow(dead_code)]
+    pub fn render_model_tiled(
+        &mut self,
+        model: &crate::model::Model,
+        texture_image: &TGAImage,
+        tile_size: i32,
+        mut on_tile: impl FnMut(Rect, &TGAImage),
+    ) -> Result<RenderStats, RenderError> {
+        assert!(tile_size > 0, "Tile size must be positive");
+        let mut zbuffer = self.new_zbuffer();
+        self.reset_id_buffer();
+        let model_matrix = self.model_matrix.clone();
+        self.update_shadow_map(&[(model, &model_matrix)]);
+
+        let mut stats = RenderStats::default();
+        for y in (0..self.image.height).step_by(tile_size as usize) {
+            for x in (0..self.image.width).step_by(tile_size as usize) {
+                let tile = Rect {
+                    x,
+                    y,
+                    width: tile_size.min(self.image.width - x),
+                    height: tile_size.min(self.image.height - y),
                 };
-                let p_cur = p_a + (p_b - p_a) * phi;
-                let uvp_cur = uvp_a + (uvp_b - uvp_a) * phi;
-
-                if zbuffer[p_cur.x as usize][p_cur.y as usize] < p_cur.z as i32 {
-                    zbuffer[p_cur.x as usize][p_cur.y as usize] = p_cur.z as i32;
-                    let color = match texture_image.get(
-                        (uvp_cur.x.abs() * texture_image.width as f32) as i32,
-                        (uvp_cur.y.abs() * texture_image.height as f32) as i32,
-                    ) {
-                        Some(c) => c,
-                        None => {
-                            return Err(format!(
-                                "Texture not found. p_cur: {}, uvp_cur: {}",
-                                p_cur, uvp_cur
-                            ))
-                        }
-                    };
-                    let [b, g, r, a] = color.raw;
-                    image.set(
-                        p_cur.x as i32,
-                        p_cur.y as i32,
-                        &TGAColor::rgba(
-                            (r as f32 * intensity) as u8,
-                            (g as f32 * intensity) as u8,
-                            (b as f32 * intensity) as u8,
-                            a,
-                        ),
-                    );
-                }
+                self.tile = Some(tile);
+                self.stats = RenderStats::default();
+                let filled =
+                    self.fill_model(model, texture_image, &model_matrix, &mut zbuffer, None);
+                self.tile = None;
+                filled?;
+                // Every tile sees every face, so only the fragment counts add up
+                stats = RenderStats {
+                    pixels_shaded: stats.pixels_shaded + self.stats.pixels_shaded,
+                    depth_tests_failed: stats.depth_tests_failed + self.stats.depth_tests_failed,
+                    ..self.stats
+                };
+                on_tile(tile, &self.image);
             }
         }
 
-        Ok(())
+        self.stats = stats;
+        self.depth_buffer = Some(zbuffer);
+        Ok(stats)
     }
 
+    /// Renders `model` like `render_model`, using the texture set with `set_texture`.
     #[allow(dead_code)]
-    pub fn draw_line(
+    pub fn render_model_with_stored_texture(
         &mut self,
-        t0: Vec2i,
-        t1: Vec2i,
-        color: &TGAColor,
-        optimization_level: OptimizationLevel,
-    ) {
-        let image = &mut self.image;
-        let mut steep = false;
-        let mut x0 = t0.x;
-        let mut x1 = t1.x;
-        let mut y0 = t0.y;
-        let mut y1 = t1.y;
+        model: &crate::model::Model,
+    ) -> Result<RenderStats, RenderError> {
+        let mut white = TGAImage::new(1, 1, Format::RGB);
+        white.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        // Taken out for the call so the renderer can be borrowed mutably
+        let stored = self.texture.take();
+        let texture_image = stored.as_ref().unwrap_or(&white);
+        let result = self.render_model(model, texture_image, None);
+        self.texture = stored;
+        result
+    }
 
-        if (x0 - x1).abs() < (y0 - y1).abs() {
-            std::mem::swap(&mut x0, &mut y0);
-            std::mem::swap(&mut x1, &mut y1);
-            steep = true;
+    /// Renders `model` like `render_model` without a texture, lighting `base_color` instead, so
+    /// models without uvs or a diffuse map can be drawn. Faces whose material has a diffuse map
+    /// still use it.
+    #[allow(dead_code)]
+    pub fn render_model_flat(
+        &mut self,
+        model: &crate::model::Model,
+        base_color: TGAColor,
+    ) -> Result<RenderStats, RenderError> {
+        // A single texel reads the same at every uv
+        let mut texture_image = TGAImage::new(1, 1, Format::RGB);
+        texture_image.set(0, 0, &base_color);
+        self.render_model(model, &texture_image, None)
+    }
+
+    /// Renders `model` with the diffuse texture named by its material library, loaded from the
+    /// path resolved against the OBJ file. Models without one use the stored texture instead.
+    /// Fails if a material library the model names could not be read.
+    #[allow(dead_code)]
+    pub fn render_model_auto(
+        &mut self,
+        model: &crate::model::Model,
+    ) -> Result<RenderStats, RenderError> {
+        if let Some(path) = model.missing_material_libs().first() {
+            return Err(RenderError::MissingMaterialLibrary(path.clone()));
         }
+        let Some(path) = model.diffuse_map() else {
+            return self.render_model_with_stored_texture(model);
+        };
+        let mut texture_image = TGAImage::new(0, 0, Format::RGB);
+        path.to_str()
+            .and_then(|p| texture_image.read_tga_file(p).ok())
+            .ok_or_else(|| RenderError::MissingTexture(path.to_path_buf()))?;
 
-        if x0 > x1 {
-            std::mem::swap(&mut x0, &mut x1);
-            std::mem::swap(&mut y0, &mut y1);
+        self.render_model(model, &texture_image, None)
+    }
+
+    /// Renders several models into one image, sharing a single z-buffer so they occlude each
+    /// other. Each item carries the model, its texture and its model-to-world matrix.
+    #[allow(dead_code)]
+    pub fn render_scene(
+        &mut self,
+        items: &[(&crate::model::Model, &TGAImage, Matrix)],
+    ) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        self.reset_id_buffer();
+        let casters: Vec<_> = items.iter().map(|(model, _, m)| (*model, m)).collect();
+        self.update_shadow_map(&casters);
+        for (model, texture_image, model_matrix) in items {
+            self.fill_model(model, texture_image, model_matrix, &mut zbuffer, None)?;
         }
 
-        match optimization_level {
-            OptimizationLevel::Level0 => {
-                for x in x0..=x1 {
-                    let t = (x - x0) as f32 / (x1 - x0) as f32;
-                    let y = y0 as f32 + t * (y1 - y0) as f32;
-                    if steep {
-                        image.set(y as i32, x, color);
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Renders like `render_model`, then outlines every drawn triangle in `edge_color`. Edge
+    /// pixels are depth tested against the filled surface, so edges behind other faces stay hidden.
+    #[allow(dead_code)]
+    pub fn render_model_hidden_line(
+        &mut self,
+        model: &crate::model::Model,
+        texture_image: &TGAImage,
+        edge_color: &TGAColor,
+    ) -> Result<(), RenderError> {
+        // Lets edges win against the surface they lie on despite interpolation differences
+        let bias = 0.005 * self.depth_scale;
+        let mut zbuffer = self.new_zbuffer();
+        self.reset_id_buffer();
+        let model_matrix = self.model_matrix.clone();
+        self.update_shadow_map(&[(model, &model_matrix)]);
+        let triangles = self.fill_model(model, texture_image, &model_matrix, &mut zbuffer, None)?;
+
+        for triangle in triangles {
+            for j in 0..3 {
+                let (a, b) = (triangle[j], triangle[(j + 1) % 3]);
+                let steps = (b.x - a.x).abs().max((b.y - a.y).abs()) as i32;
+                for k in 0..=steps {
+                    let t = if steps == 0 {
+                        0.0
                     } else {
-                        image.set(x, y as i32, color);
+                        k as f32 / steps as f32
+                    };
+                    let p = a.lerp(b, t);
+                    let (x, y) = (p.x.round() as i32, p.y.round() as i32);
+                    let Some(i) = self.depth_index(x, y) else {
+                        continue;
+                    };
+                    if !self.nearer(zbuffer[i], p.z + self.depth_delta(bias)) {
+                        self.image.set(x, y, edge_color);
                     }
                 }
             }
-            OptimizationLevel::Level1 => {
-                let dx = x1 - x0;
-                let dy = y1 - y0;
-                let derror = dy as f32 / dx as f32;
-                let mut error: f32 = 0.0;
-                let mut y = y0;
-                for x in x0..=x1 {
-                    if steep {
-                        image.set(y, x, color);
-                    } else {
-                        image.set(x, y, color);
-                    }
-                    error += derror;
-                    if error > 0.5 {
-                        y += if y1 > y0 { 1 } else { -1 };
-                        error -= 1.0;
-                    }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Rasterizes the lit faces of `model`, placed in the world by `model_matrix`, and returns
+    /// their screen-space triangles. Faces with a material use its textures from
+    /// `material_textures`, and the others `texture_image`.
+    fn fill_model(
+        &mut self,
+        model: &crate::model::Model,
+        texture_image: &TGAImage,
+        model_matrix: &Matrix,
+        zbuffer: &mut [f32],
+        progress: Option<&dyn Fn(f32)>,
+    ) -> Result<Vec<[Vec3f; 3]>, RenderError> {
+        let normal_matrix = Self::normal_matrix(model_matrix);
+        let mipmaps = match self.texture_filter {
+            TextureFilter::Nearest => Vec::new(),
+            TextureFilter::Trilinear => texture_image.generate_mipmaps(),
+        };
+        let material_textures = self.material_textures(model, texture_image)?;
+        let viewport = self.screen_viewport()?;
+        let mut triangles = Vec::new();
+        let nfaces = model.nfaces();
+        let report_every = (nfaces / 100).max(1);
+
+        for i in 0..nfaces {
+            if let Some(progress) = progress {
+                if i % report_every == 0 {
+                    progress(i as f32 / nfaces as f32);
                 }
             }
-            OptimizationLevel::Level2 => {
-                let dx = x1 - x0;
-                let dy = y1 - y0;
-                let derror = dy.abs() * 2;
-                let mut error = 0;
-                let mut y = y0;
-                for x in x0..=x1 {
-                    if steep {
-                        image.set(y, x, color);
-                    } else {
-                        image.set(x, y, color);
-                    }
-                    error += derror;
-                    if error > dx {
-                        y += if y1 > y0 { 1 } else { -1 };
-                        error -= dx * 2;
+            let face = model.face(i);
+            self.stats.faces_total += 1;
+            let mut culled_all = true;
+            let mut clipped = false;
+            let (texture_image, mipmaps) = match model
+                .face_material(i)
+                .and_then(|m| material_textures[m].as_ref())
+            {
+                Some((texture, mipmaps)) => (texture, mipmaps),
+                None => (texture_image, &mipmaps),
+            };
+            for corners in Self::split_face(model, i) {
+                let mut screen_coords = [Vec3f::new(0.0, 0.0, 0.0); 3];
+                let mut world_coords = [Vec3f::new(0.0, 0.0, 0.0); 3];
+                let mut texture_coords = [Vec2f::new(0.0, 0.0); 3];
+                for (j, &k) in corners.iter().enumerate() {
+                    let v = model_matrix.transform_point(model.vert(face[k][0]));
+                    screen_coords[j] = self.to_screen(&viewport, v);
+                    world_coords[j] = v;
+                    // Faces without uvs sample the texture's corner
+                    let mut uv = model.try_uv(face[k][1]).unwrap_or(Vec2f::new(0.0, 0.0));
+                    if self.flip_texture_v {
+                        uv.y = 1.0 - uv.y;
                     }
+                    texture_coords[j] = Vec2f::new(
+                        uv.x * self.uv_scale.x + self.uv_offset.x,
+                        uv.y * self.uv_scale.y + self.uv_offset.y,
+                    );
+                }
+                let winding_sign = match self.front_face {
+                    Winding::CounterClockwise => 1.0,
+                    Winding::Clockwise => -1.0,
+                };
+                let n = (world_coords[1] - world_coords[0])
+                    .cross(world_coords[2] - world_coords[0])
+                    .normalize(1.0)
+                    * winding_sign;
+                // Front faces keep the configured winding on screen
+                let front_facing =
+                    Self::signed_area(screen_coords[0], screen_coords[1], screen_coords[2])
+                        * winding_sign
+                        > 0.0;
+                let culled = match self.cull_mode {
+                    CullMode::None => false,
+                    CullMode::Back => !front_facing && !self.double_sided,
+                    CullMode::Front => front_facing,
+                };
+                if culled {
+                    continue;
                 }
+                culled_all = false;
+                clipped |= screen_coords.iter().any(|t| {
+                    t.x < 0.0
+                        || t.y < 0.0
+                        || t.x > self.image.width as f32
+                        || t.y > self.image.height as f32
+                });
+                // Double-sided faces seen from behind are lit as if they faced the viewer
+                let side = if self.double_sided && !front_facing {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                let intensities = match self.shading {
+                    ShadingMode::Flat => [self.light_intensity(n * side); 3],
+                    ShadingMode::Gouraud => corners.map(|k| {
+                        let normal = match model.try_normal(face[k][2]) {
+                            Some(normal) => {
+                                Self::transform_direction(&normal_matrix, normal).normalize(1.0)
+                            }
+                            None => n,
+                        };
+                        self.light_intensity(normal * side)
+                    }),
+                };
+
+                let draw = match (self.edge_antialiasing, self.rasterizer) {
+                    (true, _) => Self::draw_triangle_aa,
+                    (false, Rasterizer::Scanline) => Self::draw_triangle,
+                    (false, Rasterizer::Barycentric) => Self::draw_triangle_barycentric,
+                };
+                draw(
+                    self,
+                    screen_coords[0],
+                    screen_coords[1],
+                    screen_coords[2],
+                    texture_coords[0],
+                    texture_coords[1],
+                    texture_coords[2],
+                    texture_image,
+                    mipmaps,
+                    intensities,
+                    i,
+                    zbuffer,
+                )?;
+                self.stats.triangles_drawn += 1;
+                triangles.push(screen_coords);
             }
+            self.stats.faces_culled += culled_all as usize;
+            self.stats.faces_clipped += clipped as usize;
         }
+        if let Some(progress) = progress {
+            progress(1.0);
+        }
+
+        Ok(triangles)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Loads the diffuse map of every material a face of `model` uses, multiplied by the
+    /// material's diffuse color, along with its mipmaps when filtering needs them. Materials
+    /// without a map tint `fallback` instead; untinted ones are `None` and use it as is.
+    #[allow(clippy::type_complexity)]
+    fn material_textures(
+        &self,
+        model: &crate::model::Model,
+        fallback: &TGAImage,
+    ) -> Result<Vec<Option<(TGAImage, Vec<TGAImage>)>>, RenderError> {
+        let mut used = vec![false; model.materials().len()];
+        for i in 0..model.nfaces() {
+            if let Some(m) = model.face_material(i) {
+                used[m] = true;
+            }
+        }
 
-    #[test]
-    fn test_draw_line() {
-        let width = 10;
-        let height = 5;
-        let c = &TGAColor::rgba(128, 1, 255, 255);
+        let mut textures = Vec::new();
+        for (material, used) in model.materials().iter().zip(used) {
+            if !used {
+                textures.push(None);
+                continue;
+            }
+            let mut texture = match &material.diffuse_map {
+                Some(path) => {
+                    let mut texture = TGAImage::new(0, 0, Format::RGB);
+                    path.to_str()
+                        .and_then(|p| texture.read_tga_file(p).ok())
+                        .ok_or_else(|| RenderError::MissingTexture(path.clone()))?;
+                    texture
+                }
+                None if material.diffuse_color == [1.0; 3] => {
+                    textures.push(None);
+                    continue;
+                }
+                None => fallback.clone(),
+            };
 
-        struct TestCase<'a> {
-            optimization_level: OptimizationLevel,
-            filename: &'a str,
+            let [kr, kg, kb] = material.diffuse_color;
+            for y in 0..texture.height {
+                for x in 0..texture.width {
+                    let Some(texel) = texture.get(x, y) else {
+                        continue;
+                    };
+                    let [b, g, r, a] = texel.raw;
+                    let tint = |c: u8, k: f32| clamp_u8(c as f32 * k);
+                    texture.set(
+                        x,
+                        y,
+                        &TGAColor::rgba(tint(r, kr), tint(g, kg), tint(b, kb), a),
+                    );
+                }
+            }
+            let mipmaps = match self.texture_filter {
+                TextureFilter::Nearest => Vec::new(),
+                TextureFilter::Trilinear => texture.generate_mipmaps(),
+            };
+            textures.push(Some((texture, mipmaps)));
         }
-        for test in vec![
-            TestCase {
-                optimization_level: OptimizationLevel::Level0,
-                filename: "tests/images/line0.tga",
-            },
-            TestCase {
-                optimization_level: OptimizationLevel::Level1,
-                filename: "tests/images/line1.tga",
-            },
-            TestCase {
+
+        Ok(textures)
+    }
+
+    /// Debug view filling every face with a flat color derived from its index, without lighting
+    /// or texturing. Overlapping or untriangulated faces stand out as color changes.
+    #[allow(dead_code)]
+    pub fn render_model_face_ids(
+        &mut self,
+        model: &crate::model::Model,
+    ) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        self.reset_id_buffer();
+
+        for i in 0..model.nfaces() {
+            for (_, [t0, t1, t2]) in self.screen_triangles(model, &viewport, i) {
+                self.draw_triangle_flat(t0, t1, t2, &Self::face_id_color(i), i, &mut zbuffer)?;
+            }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Debug view shading every face with its interpolated texture coordinates, u as red and v
+    /// as green, without lighting. Coordinates outside [0, 1] wrap so tiling shows up as repeats.
+    /// Faces with a corner lacking a uv are drawn black.
+    #[allow(dead_code)]
+    pub fn render_model_uv(&mut self, model: &crate::model::Model) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        self.reset_id_buffer();
+        let wrap = |c: f32| {
+            if (0.0..=1.0).contains(&c) {
+                c
+            } else {
+                c.rem_euclid(1.0)
+            }
+        };
+
+        for i in 0..model.nfaces() {
+            let face = model.face(i);
+            for (corners, screen_coords) in self.screen_triangles(model, &viewport, i) {
+                let [Some(uv0), Some(uv1), Some(uv2)] = corners.map(|k| model.try_uv(face[k][1]))
+                else {
+                    self.draw_triangle_shaded(
+                        screen_coords,
+                        i,
+                        &mut zbuffer,
+                        DepthPass::Standard,
+                        |_| TGAColor::rgba(0, 0, 0, 255),
+                    );
+                    continue;
+                };
+                self.draw_triangle_shaded(
+                    screen_coords,
+                    i,
+                    &mut zbuffer,
+                    DepthPass::Standard,
+                    |[w0, w1, w2]| {
+                        let uv = uv0 * w0 + uv1 * w1 + uv2 * w2;
+                        TGAColor::rgba(
+                            clamp_u8(wrap(uv.x) * 255.0),
+                            clamp_u8(wrap(uv.y) * 255.0),
+                            0,
+                            255,
+                        )
+                    },
+                );
+            }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Debug view shading every face with its world-space normal mapped from [-1, 1] to RGB.
+    /// Vertex normals are interpolated when the face has them, otherwise the face normal is used.
+    #[allow(dead_code)]
+    pub fn render_model_normals(&mut self, model: &crate::model::Model) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        self.reset_id_buffer();
+
+        for i in 0..model.nfaces() {
+            let normals = self.world_normals(model, i);
+            for (corners, screen_coords) in self.screen_triangles(model, &viewport, i) {
+                let [n0, n1, n2] = corners.map(|k| normals[k]);
+                self.draw_triangle_shaded(
+                    screen_coords,
+                    i,
+                    &mut zbuffer,
+                    DepthPass::Standard,
+                    |[w0, w1, w2]| {
+                        let n = (n0 * w0 + n1 * w1 + n2 * w2).normalize(1.0);
+                        TGAColor::rgba(
+                            clamp_u8((n.x * 0.5 + 0.5) * 255.0),
+                            clamp_u8((n.y * 0.5 + 0.5) * 255.0),
+                            clamp_u8((n.z * 0.5 + 0.5) * 255.0),
+                            255,
+                        )
+                    },
+                );
+            }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Debug view coloring every fragment by its barycentric weights, so each triangle fades from
+    /// red at its first vertex to green at the second and blue at the third. No lighting.
+    #[allow(dead_code)]
+    pub fn render_model_barycentric_debug(
+        &mut self,
+        model: &crate::model::Model,
+    ) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        self.reset_id_buffer();
+
+        for i in 0..model.nfaces() {
+            for (_, screen_coords) in self.screen_triangles(model, &viewport, i) {
+                self.draw_triangle_shaded(
+                    screen_coords,
+                    i,
+                    &mut zbuffer,
+                    DepthPass::Standard,
+                    |[w0, w1, w2]| {
+                        TGAColor::rgba(
+                            clamp_u8(w0 * 255.0),
+                            clamp_u8(w1 * 255.0),
+                            clamp_u8(w2 * 255.0),
+                            255,
+                        )
+                    },
+                );
+            }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Debug view drawing every vertex of `model` as a `size`x`size` square of `color`, placed
+    /// by the model matrix and the same screen mapping as `render_model`. Points are depth
+    /// tested against each other.
+    #[allow(dead_code)]
+    pub fn render_points(
+        &mut self,
+        model: &crate::model::Model,
+        color: &TGAColor,
+        size: i32,
+    ) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        for &v in model.verts() {
+            let p = self.to_screen(&viewport, self.model_matrix.transform_point(v));
+            let (x, y, z) = (p.x as i32, p.y as i32, p.z);
+
+            let (left, bottom) = (x - size / 2, y - size / 2);
+            for px in left.max(0)..(left + size).min(self.image.width) {
+                for py in bottom.max(0)..(bottom + size).min(self.image.height) {
+                    let depth = &mut zbuffer[(px + py * self.image.width) as usize];
+                    if self.mask_allows(px, py) && self.nearer(z, *depth) {
+                        *depth = z;
+                        self.image.set(px, py, color);
+                    }
+                }
+            }
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Draws the model colored by `fragment`, called with the face index, the corners of the
+    /// face the triangle being drawn spans and the fragment's barycentric weights relative to
+    /// those corners. With the depth prepass enabled, a depth-only pass over every face
+    /// runs first and `fragment` is only called where a fragment matches the final depth.
+    #[allow(dead_code)]
+    pub fn render_model_shaded(
+        &mut self,
+        model: &crate::model::Model,
+        fragment: impl Fn(usize, [usize; 3], [f32; 3]) -> TGAColor,
+    ) -> Result<(), RenderError> {
+        let mut zbuffer = self.new_zbuffer();
+        let viewport = self.screen_viewport()?;
+        self.reset_id_buffer();
+
+        let triangles: Vec<(usize, [usize; 3], [Vec3f; 3])> = (0..model.nfaces())
+            .flat_map(|i| {
+                self.screen_triangles(model, &viewport, i)
+                    .into_iter()
+                    .map(move |(corners, screen_coords)| (i, corners, screen_coords))
+            })
+            .collect();
+
+        let pass = if self.depth_prepass {
+            for &(i, _, screen_coords) in &triangles {
+                self.draw_triangle_shaded(
+                    screen_coords,
+                    i,
+                    &mut zbuffer,
+                    DepthPass::DepthOnly,
+                    |_| TGAColor::new(),
+                );
+            }
+            DepthPass::Equal
+        } else {
+            DepthPass::Standard
+        };
+        for &(i, corners, screen_coords) in &triangles {
+            self.draw_triangle_shaded(screen_coords, i, &mut zbuffer, pass, |w| {
+                fragment(i, corners, w)
+            });
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Cartoon-style render in gray: the light intensity, interpolated from the vertices, is
+    /// rounded up to one of `levels` flat bands, and outlines are drawn in black wherever the
+    /// normal buffer of `render_model_normals` has a strong Sobel gradient, which covers both
+    /// silhouettes and creases.
+    #[allow(dead_code)]
+    pub fn render_model_toon(
+        &mut self,
+        model: &crate::model::Model,
+        levels: u32,
+    ) -> Result<(), RenderError> {
+        let blank = TGAImage::new(self.width, self.height, Format::RGB);
+        let image = std::mem::replace(&mut self.image, blank);
+        let normals = self.render_model_normals(model);
+        let edges = std::mem::replace(&mut self.image, image).sobel();
+        normals?;
+
+        let intensities: Vec<Vec<f32>> = (0..model.nfaces())
+            .map(|i| {
+                self.world_normals(model, i)
+                    .into_iter()
+                    .map(|n| self.light_intensity(n).clamp(0.0, 1.0))
+                    .collect()
+            })
+            .collect();
+        let levels = levels.max(1) as f32;
+        self.render_model_shaded(model, |i, corners, [w0, w1, w2]| {
+            let [a, b, c] = corners.map(|k| intensities[i][k]);
+            let band = ((a * w0 + b * w1 + c * w2) * levels).ceil() / levels;
+            let v = clamp_u8((band * 255.0).round());
+            TGAColor::rgba(v, v, v, 255)
+        })?;
+
+        let black = TGAColor::rgba(0, 0, 0, 255);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if edges
+                    .get(x, y)
+                    .is_some_and(|e| e.raw[0] > TOON_OUTLINE_THRESHOLD)
+                {
+                    self.image.set(x, y, &black);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// For each vertex, whether the edge opposite it owns the pixel centers lying on it: a top or
+    /// left edge once the triangle is oriented counter-clockwise. A shared edge runs in opposite
+    /// directions in its two triangles, so exactly one of them owns it. `area` is twice the
+    /// signed area of the triangle.
+    fn owned_edges([t0, t1, t2]: [Vec3f; 3], area: f32) -> [bool; 3] {
+        let owns = |a: Vec3f, b: Vec3f| {
+            let (dx, dy) = ((b.x - a.x) * area.signum(), (b.y - a.y) * area.signum());
+            dy > 0.0 || (dy == 0.0 && dx < 0.0)
+        };
+        [owns(t1, t2), owns(t2, t0), owns(t0, t1)]
+    }
+
+    /// Rasterizes a triangle at pixel centers, coloring each fragment with `shade` called on its
+    /// barycentric weights relative to `t0`, `t1` and `t2`. Centers lying exactly on an edge
+    /// follow the top-left rule, so triangles sharing an edge cover each of its pixels once.
+    fn draw_triangle_shaded(
+        &mut self,
+        [t0, t1, t2]: [Vec3f; 3],
+        face: usize,
+        zbuffer: &mut [f32],
+        pass: DepthPass,
+        shade: impl Fn([f32; 3]) -> TGAColor,
+    ) {
+        let area = 2.0 * Self::signed_area(t0, t1, t2);
+        if area.abs() < 2.0 * MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return;
+        }
+        let [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+
+        let owned = Self::owned_edges([t0, t1, t2], area);
+
+        let x_min = (t0.x.min(t1.x).min(t2.x).floor() as i32).max(0);
+        let x_max = (t0.x.max(t1.x).max(t2.x).ceil() as i32).min(self.image.width - 1);
+        let y_min = (t0.y.min(t1.y).min(t2.y).floor() as i32).max(0);
+        let y_max = (t0.y.max(t1.y).max(t2.y).ceil() as i32).min(self.image.height - 1);
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: Vec3f, b: Vec3f| (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x);
+                let w0 = edge(t1, t2) / area;
+                let w1 = edge(t2, t0) / area;
+                let w2 = edge(t0, t1) / area;
+                let inside = [w0, w1, w2]
+                    .iter()
+                    .zip(owned)
+                    .all(|(&w, owned)| w > 0.0 || (w == 0.0 && owned));
+                if !inside {
+                    continue;
+                }
+
+                let z = t0.z * w0 + t1.z * w1 + t2.z * w2;
+                let depth = &mut zbuffer[(x + y * self.image.width) as usize];
+                let passed = match pass {
+                    DepthPass::Standard | DepthPass::DepthOnly => self.nearer(z, *depth),
+                    DepthPass::Equal => (*depth - z).abs() <= DEPTH_EQUAL_EPSILON,
+                };
+                if !self.mask_allows(x, y) || !passed {
+                    continue;
+                }
+                if pass != DepthPass::Equal {
+                    *depth = z;
+                }
+                if pass == DepthPass::DepthOnly {
+                    continue;
+                }
+                if let Some(ids) = self.id_buffer.as_mut() {
+                    ids[(x + y * self.image.width) as usize] = face as i32;
+                }
+
+                self.image.set(x, y, &shade([w0, w1, w2]));
+            }
+        }
+    }
+
+    /// Spreads consecutive face indices around the hue circle by the golden ratio so neighbouring
+    /// faces get clearly different colors.
+    fn face_id_color(idx: usize) -> TGAColor {
+        TGAColor::from_hsv((idx as f32 * 0.618_034).fract() * 360.0, 0.75, 1.0)
+    }
+
+    #[allow(dead_code)]
+    pub fn render_model_with_camera(
+        &mut self,
+        model: &crate::model::Model,
+        camera_mode: CameraMode,
+    ) -> Result<(), RenderError> {
+        let light_dir = Vec3f::new(1.0, -1.0, 1.0).normalize(1.0);
+        let aspect = self.width as f32 / self.height as f32;
+        self.projection = match camera_mode {
+            CameraMode::Perspective { fov, near, far } => {
+                Matrix::perspective(fov, aspect, near, far)
+            }
+            CameraMode::Orthographic { size, near, far } => {
+                Matrix::orthographic(size, aspect, near, far)
+            }
+        };
+
+        self.check_viewport(&self.viewport)?;
+        let mut zbuffer = self.new_zbuffer();
+        self.reset_id_buffer();
+        let transform = self.viewport.clone() * self.projection.clone() * self.view.clone();
+
+        for i in 0..model.nfaces() {
+            let face = model.face(i);
+            let mut screen_coords = [Vec3f::new(0.0, 0.0, 0.0); 3];
+            let mut world_coords = [Vec3f::new(0.0, 0.0, 0.0); 3];
+            let mut normals = [0.0_f32; 3];
+            for j in 0..3 {
+                let v = model.vert(face[j][0]);
+                let p = transform.transform_point(v);
+                screen_coords[j] = Vec3f::new(p.x.trunc(), p.y.trunc(), self.to_depth(p.z));
+                let n = model
+                    .try_normal(face[j][2])
+                    .unwrap_or_else(|| model.face_normal(i));
+                normals[j] = n.normalize(1.0).dot(light_dir);
+                world_coords[j] = v;
+            }
+            self.draw_triangle_with_normal(
+                screen_coords[0],
+                screen_coords[1],
+                screen_coords[2],
+                normals[0],
+                normals[1],
+                normals[2],
+                i,
+                &mut zbuffer,
+            )?;
+        }
+
+        self.depth_buffer = Some(zbuffer);
+        Ok(())
+    }
+
+    /// Renders `model` through `render_model_with_camera` from `frames` camera positions evenly
+    /// spaced on a full circle around the y axis, starting at the default camera position and
+    /// looking at the origin. Frames are saved to `out_dir` as `frame_000.tga`,
+    /// `frame_001.tga`, ...; the view matrix is left at the last frame's camera.
+    #[allow(dead_code)]
+    pub fn render_turntable(
+        &mut self,
+        model: &crate::model::Model,
+        frames: usize,
+        out_dir: &str,
+    ) -> std::io::Result<()> {
+        let start = Vec3f::new(3.0, 1.0, 3.0);
+        self.render_frames(model, frames, out_dir, |frame| {
+            let angle = std::f32::consts::TAU * frame as f32 / frames as f32;
+            let (sin, cos) = angle.sin_cos();
+            let eye = Vec3f::new(
+                start.x * cos + start.z * sin,
+                start.y,
+                start.z * cos - start.x * sin,
+            );
+            (eye, Vec3f::new(0.0, 0.0, 0.0))
+        })
+    }
+
+    /// Renders `model` like `render_turntable`, but from `frames` cameras sampled evenly along
+    /// `path`, the first at its start and the last at its end.
+    #[allow(dead_code)]
+    pub fn render_camera_path(
+        &mut self,
+        model: &crate::model::Model,
+        path: &CameraPath,
+        frames: usize,
+        out_dir: &str,
+    ) -> std::io::Result<()> {
+        self.render_frames(model, frames, out_dir, |frame| {
+            path.sample(frame as f32 / frames.saturating_sub(1).max(1) as f32)
+        })
+    }
+
+    /// Renders and saves `frames` frames, looking from the `(eye, center)` `camera` returns for
+    /// each frame index.
+    fn render_frames(
+        &mut self,
+        model: &crate::model::Model,
+        frames: usize,
+        out_dir: &str,
+        camera: impl Fn(usize) -> (Vec3f, Vec3f),
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(out_dir)?;
+        for frame in 0..frames {
+            let (eye, center) = camera(frame);
+            self.set_camera(eye, center, Vec3f::new(0.0, 1.0, 0.0));
+
+            // The z-buffer is fresh for every render, but the framebuffer is not
+            self.image.clear();
+            self.render_model_with_camera(
+                model,
+                CameraMode::Perspective {
+                    fov: std::f32::consts::FRAC_PI_6,
+                    near: 0.1,
+                    far: 100.0,
+                },
+            )
+            .map_err(std::io::Error::other)?;
+            let path = std::path::Path::new(out_dir).join(format!("frame_{:03}.tga", frame));
+            self.save_tga_image(&path.to_string_lossy())?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders the shadow map of the first light over every face of `items`, or drops it when
+    /// shadows are off.
+    fn update_shadow_map(&mut self, items: &[(&crate::model::Model, &Matrix)]) {
+        self.shadow_map = match (self.shadows, self.lights.first()) {
+            (true, Some(&light)) => {
+                let mut triangles = Vec::new();
+                for &(model, model_matrix) in items {
+                    for i in 0..model.nfaces() {
+                        let face = model.face(i);
+                        for corners in Self::split_face(model, i) {
+                            triangles.push(
+                                corners
+                                    .map(|k| model_matrix.transform_point(model.vert(face[k][0]))),
+                            );
+                        }
+                    }
+                }
+                let size = self.width.max(self.height) as usize;
+                Some(ShadowMap::new(&triangles, light, size))
+            }
+            _ => None,
+        };
+    }
+
+    /// Attenuates the diffuse part of `intensity` for the fragment at pixel `(x, y)` with depth
+    /// `z` by the fraction of the shadow map that sees it lit.
+    fn shadowed(&self, intensity: f32, x: f32, y: f32, z: f32) -> f32 {
+        let Some(map) = &self.shadow_map else {
+            return intensity;
+        };
+        // Inverse of the screen mapping in `fill_model`
+        let p = Vec3f::new(
+            x * 2.0 / self.width as f32 - 1.0,
+            y * 2.0 / self.height as f32 - 1.0,
+            self.to_depth(z) * 2.0 / self.depth_scale - 1.0,
+        );
+        self.ambient + (intensity - self.ambient) * map.lit_fraction(p, self.shadow_pcf)
+    }
+
+    /// A z-buffer for the current image, one depth per pixel indexed by `x + y * width` like the
+    /// framebuffer, cleared to the clear depth.
+    fn new_zbuffer(&self) -> Vec<f32> {
+        let far = self.to_depth(0.0);
+        vec![self.clear_depth.unwrap_or(far); (self.image.width * self.image.height) as usize]
+    }
+
+    /// Index of pixel `(x, y)` in a z-buffer, or `None` if it lies outside the image.
+    fn depth_index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && y >= 0 && x < self.image.width && y < self.image.height)
+            .then(|| (x + y * self.image.width) as usize)
+    }
+
+    /// Converts a screen-space z, 0 at the far end of the depth range and larger when nearer,
+    /// to the z-buffer convention; converting twice gives the original value back.
+    fn to_depth(&self, z: f32) -> f32 {
+        if self.reverse_z {
+            z
+        } else {
+            self.depth_scale - z
+        }
+    }
+
+    /// Converts a change in screen-space z, positive towards the viewer, to the z-buffer
+    /// convention.
+    fn depth_delta(&self, dz: f32) -> f32 {
+        if self.reverse_z {
+            dz
+        } else {
+            -dz
+        }
+    }
+
+    /// Viewport taking normalized device coordinates to the whole image, or to the rectangle
+    /// of the viewport given to `set_viewport`, with depths spanning the depth range. Fails like
+    /// `check_viewport` if that rectangle leaves the framebuffer.
+    fn screen_viewport(&self) -> Result<Matrix, RenderError> {
+        let mut viewport = Matrix::viewport(0, 0, self.width, self.height, self.depth_scale);
+        if self.viewport_set {
+            viewport[0] = self.viewport[0].clone();
+            viewport[1] = self.viewport[1].clone();
+        }
+        self.check_viewport(&viewport)?;
+        Ok(viewport)
+    }
+
+    /// Maps `v` through `viewport` onto whole pixels and a depth in the z-buffer convention.
+    fn to_screen(&self, viewport: &Matrix, v: Vec3f) -> Vec3f {
+        let p = viewport.transform_point(v);
+        Vec3f::new(p.x.trunc(), p.y.trunc(), self.to_depth(p.z))
+    }
+
+    /// Moves the depths of a screen-space triangle by the polygon offset.
+    fn offset_depth(&self, [t0, t1, t2]: [Vec3f; 3]) -> [Vec3f; 3] {
+        let (factor, units) = self.polygon_offset;
+        if factor == 0.0 && units == 0.0 {
+            return [t0, t1, t2];
+        }
+        // Depth gradient of the triangle's plane
+        let (e1, e2) = (t1 - t0, t2 - t0);
+        let det = e1.x * e2.y - e1.y * e2.x;
+        let slope = if det == 0.0 {
+            0.0
+        } else {
+            let dz_dx = (e1.z * e2.y - e2.z * e1.y) / det;
+            let dz_dy = (e2.z * e1.x - e1.z * e2.x) / det;
+            dz_dx.abs().max(dz_dy.abs())
+        };
+        let offset = self.depth_delta(-(factor * slope + units));
+        [t0, t1, t2].map(|t| Vec3f::new(t.x, t.y, t.z + offset))
+    }
+
+    /// Whether stored depth `a` is nearer than `b` under the z-buffer convention.
+    fn nearer(&self, a: f32, b: f32) -> bool {
+        if self.reverse_z {
+            a > b
+        } else {
+            a < b
+        }
+    }
+
+    /// Ambient light plus the diffuse contribution of every light for a surface with unit
+    /// normal `n`.
+    fn light_intensity(&self, n: Vec3f) -> f32 {
+        self.ambient
+            + self
+                .lights
+                .iter()
+                .map(|&l| (-n.dot(l)).max(0.0))
+                .sum::<f32>()
+    }
+
+    /// Unit normals at the corners of face `idx` in world space, falling back to the face normal
+    /// where the face has no vertex normal.
+    fn world_normals(&self, model: &crate::model::Model, idx: usize) -> Vec<Vec3f> {
+        let normal_matrix = Self::normal_matrix(&self.model_matrix);
+        model
+            .face(idx)
+            .iter()
+            .map(|corner| {
+                let n = model
+                    .try_normal(corner[2])
+                    .unwrap_or_else(|| model.face_normal(idx));
+                Self::transform_direction(&normal_matrix, n).normalize(1.0)
+            })
+            .collect()
+    }
+
+    /// Matrix taking normals to world space under `model_matrix`: its inverse transpose, so
+    /// normals stay perpendicular to non-uniformly scaled surfaces.
+    fn normal_matrix(model_matrix: &Matrix) -> Matrix {
+        model_matrix
+            .inverse()
+            .unwrap_or_else(|| Matrix::identity(4))
+            .transpose()
+    }
+
+    /// Applies the linear part of `m` to direction `d`, ignoring translation.
+    fn transform_direction(m: &Matrix, d: Vec3f) -> Vec3f {
+        Vec3f::new(
+            m[0][0] * d.x + m[0][1] * d.y + m[0][2] * d.z,
+            m[1][0] * d.x + m[1][1] * d.y + m[1][2] * d.z,
+            m[2][0] * d.x + m[2][1] * d.y + m[2][2] * d.z,
+        )
+    }
+
+    /// Triangles `split_face` cuts face `idx` into, each as its corner indices into the face and
+    /// its vertices placed by the model matrix and mapped through `viewport`.
+    fn screen_triangles(
+        &self,
+        model: &crate::model::Model,
+        viewport: &Matrix,
+        idx: usize,
+    ) -> Vec<([usize; 3], [Vec3f; 3])> {
+        let face = model.face(idx);
+        Self::split_face(model, idx)
+            .into_iter()
+            .map(|corners| {
+                let screen_coords = corners.map(|k| {
+                    let v = self.model_matrix.transform_point(model.vert(face[k][0]));
+                    self.to_screen(viewport, v)
+                });
+                (corners, screen_coords)
+            })
+            .collect()
+    }
+
+    /// Splits face `idx` into triangles given as corner indices into the face. Quads are cut
+    /// along their shorter diagonal, which keeps non-planar quads closer to their true shape;
+    /// larger polygons are fanned around their first corner.
+    fn split_face(model: &crate::model::Model, idx: usize) -> Vec<[usize; 3]> {
+        let face = model.face(idx);
+        if face.len() == 4 {
+            let vert = |k: usize| model.vert(face[k][0]);
+            if (vert(2) - vert(0)).norm() <= (vert(3) - vert(1)).norm() {
+                vec![[0, 1, 2], [0, 2, 3]]
+            } else {
+                vec![[0, 1, 3], [1, 2, 3]]
+            }
+        } else {
+            (1..face.len().saturating_sub(1))
+                .map(|k| [0, k, k + 1])
+                .collect()
+        }
+    }
+
+    /// Signed screen-space area of the triangle in square pixels, positive when counter-clockwise.
+    fn signed_area(t0: Vec3f, t1: Vec3f, t2: Vec3f) -> f32 {
+        ((t1.x - t0.x) * (t2.y - t0.y) - (t2.x - t0.x) * (t1.y - t0.y)) / 2.0
+    }
+
+    fn lookat(eye: Vec3f, center: Vec3f, up: Vec3f) -> Matrix {
+        let z = (eye - center).normalize(1.0);
+        let x = (up.cross(z)).normalize(1.0);
+        let y = (z.cross(x)).normalize(1.0);
+
+        Matrix::from_vec(vec![
+            vec![x.x, x.y, x.z, -x.dot(eye)],
+            vec![y.x, y.y, y.z, -y.dot(eye)],
+            vec![z.x, z.y, z.z, -z.dot(eye)],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Fails unless `viewport` maps the whole normalized device cube inside the framebuffer,
+    /// where the z-buffer can hold every fragment.
+    fn check_viewport(&self, viewport: &Matrix) -> Result<(), RenderError> {
+        let (mut x0, mut y0) = (f32::INFINITY, f32::INFINITY);
+        let (mut x1, mut y1) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let ndc = [0, 1, 2].map(|bit| if corner & (1 << bit) != 0 { 1.0 } else { -1.0 });
+            let p = viewport.transform_point(Vec3f::new(ndc[0], ndc[1], ndc[2]));
+            (x0, y0) = (x0.min(p.x), y0.min(p.y));
+            (x1, y1) = (x1.max(p.x), y1.max(p.y));
+        }
+        if x0 < 0.0 || y0 < 0.0 || x1 > self.width as f32 || y1 > self.height as f32 {
+            return Err(RenderError::ViewportOutOfBounds {
+                x0,
+                y0,
+                x1,
+                y1,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        Ok(())
+    }
+
+    fn viewport_from_rect(x: i32, y: i32, w: i32, h: i32) -> Matrix {
+        Matrix::viewport(x, y, w, h, 255.0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_triangle_with_normal(
+        &mut self,
+        mut t0: Vec3f,
+        mut t1: Vec3f,
+        mut t2: Vec3f,
+        mut ity0: f32,
+        mut ity1: f32,
+        mut ity2: f32,
+        face: usize,
+        zbuffer: &mut [f32],
+    ) -> Result<(), RenderError> {
+        if Self::signed_area(t0, t1, t2).abs() < MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return Ok(());
+        }
+        [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+
+        // Sort the vertices by y-coordinate ascending (t0.y <= t1.y <= t2.y)
+        if t0.y > t1.y {
+            std::mem::swap(&mut t0, &mut t1);
+            std::mem::swap(&mut ity0, &mut ity1);
+        }
+        if t0.y > t2.y {
+            std::mem::swap(&mut t0, &mut t2);
+            std::mem::swap(&mut ity0, &mut ity2);
+        }
+        if t1.y > t2.y {
+            std::mem::swap(&mut t1, &mut t2);
+            std::mem::swap(&mut ity1, &mut ity2);
+        }
+
+        let total_height = (t2.y - t0.y) as i32;
+        if total_height == 0 {
+            return Err(RenderError::DivisionByZero);
+        }
+
+        for i in 0..total_height {
+            let second_half = i as f32 > t1.y - t0.y || t1.y == t0.y;
+            let segment_height = if second_half {
+                t2.y - t1.y
+            } else {
+                t1.y - t0.y
+            };
+            let alpha = i as f32 / total_height as f32;
+            let beta = (i as f32 - if second_half { t1.y - t0.y } else { 0.0 }) / segment_height;
+            let mut p_a = t0.lerp(t2, alpha);
+            let mut p_b = if second_half {
+                t1.lerp(t2, beta)
+            } else {
+                t0.lerp(t1, beta)
+            };
+            let mut ity_a = ity0 + (ity2 - ity0) * alpha;
+            let mut ity_b = if second_half {
+                ity1 + (ity2 - ity1) * beta
+            } else {
+                ity0 + (ity1 - ity0) * beta
+            };
+
+            if p_a.x > p_b.x {
+                std::mem::swap(&mut p_a, &mut p_b);
+                std::mem::swap(&mut ity_a, &mut ity_b);
+            }
+
+            for j in (p_a.x as i32)..=(p_b.x as i32) {
+                let phi = if p_b.x as i32 == p_a.x as i32 {
+                    1.0
+                } else {
+                    (j as f32 - p_a.x) / (p_b.x - p_a.x)
+                };
+                let p_cur = p_a.lerp(p_b, phi);
+                let ity_cur = ity_a + (ity_b - ity_a) * phi;
+
+                let (x, y) = (p_cur.x as i32, p_cur.y as i32);
+                let Some(k) = self.depth_index(x, y) else {
+                    continue;
+                };
+                if self.mask_allows(x, y) && self.nearer(p_cur.z, zbuffer[k]) {
+                    zbuffer[k] = p_cur.z;
+                    if let Some(ids) = self.id_buffer.as_mut() {
+                        ids[k] = face as i32;
+                    }
+                    self.image.set(
+                        p_cur.x as i32,
+                        p_cur.y as i32,
+                        &TGAColor::rgba(
+                            clamp_u8(255.0 * ity_cur),
+                            clamp_u8(255.0 * ity_cur),
+                            clamp_u8(255.0 * ity_cur),
+                            255,
+                        ),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_triangle_flat(
+        &mut self,
+        mut t0: Vec3f,
+        mut t1: Vec3f,
+        mut t2: Vec3f,
+        color: &TGAColor,
+        face: usize,
+        zbuffer: &mut [f32],
+    ) -> Result<(), RenderError> {
+        if Self::signed_area(t0, t1, t2).abs() < MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return Ok(());
+        }
+        [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+
+        // Sort the vertices by y-coordinate ascending (t0.y <= t1.y <= t2.y)
+        if t0.y > t1.y {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        if t0.y > t2.y {
+            std::mem::swap(&mut t0, &mut t2);
+        }
+        if t1.y > t2.y {
+            std::mem::swap(&mut t1, &mut t2);
+        }
+
+        let total_height = (t2.y - t0.y) as i32;
+        if total_height == 0 {
+            return Err(RenderError::DivisionByZero);
+        }
+
+        for i in 0..total_height {
+            let second_half = i as f32 > t1.y - t0.y || t1.y == t0.y;
+            let segment_height = if second_half {
+                t2.y - t1.y
+            } else {
+                t1.y - t0.y
+            };
+            let alpha = i as f32 / total_height as f32;
+            let beta = (i as f32 - if second_half { t1.y - t0.y } else { 0.0 }) / segment_height;
+            let mut p_a = t0.lerp(t2, alpha);
+            let mut p_b = if second_half {
+                t1.lerp(t2, beta)
+            } else {
+                t0.lerp(t1, beta)
+            };
+
+            if p_a.x > p_b.x {
+                std::mem::swap(&mut p_a, &mut p_b);
+            }
+
+            for j in (p_a.x as i32)..=(p_b.x as i32) {
+                let phi = if p_b.x as i32 == p_a.x as i32 {
+                    1.0
+                } else {
+                    (j as f32 - p_a.x) / (p_b.x - p_a.x)
+                };
+                let p_cur = p_a.lerp(p_b, phi);
+
+                let (x, y) = (p_cur.x as i32, p_cur.y as i32);
+                let Some(k) = self.depth_index(x, y) else {
+                    continue;
+                };
+                if self.mask_allows(x, y) && self.nearer(p_cur.z, zbuffer[k]) {
+                    zbuffer[k] = p_cur.z;
+                    if let Some(ids) = self.id_buffer.as_mut() {
+                        ids[k] = face as i32;
+                    }
+                    self.image.set(p_cur.x as i32, p_cur.y as i32, color);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_triangle(
+        &mut self,
+        mut t0: Vec3f,
+        mut t1: Vec3f,
+        mut t2: Vec3f,
+        mut uv0: Vec2f,
+        mut uv1: Vec2f,
+        mut uv2: Vec2f,
+        texture_image: &TGAImage,
+        mipmaps: &[TGAImage],
+        intensities: [f32; 3],
+        face: usize,
+        zbuffer: &mut [f32],
+    ) -> Result<(), RenderError> {
+        if Self::signed_area(t0, t1, t2).abs() < MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return Ok(());
+        }
+        [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+
+        let lod = Self::uv_lod([t0, t1, t2], [uv0, uv1, uv2], texture_image);
+        let [mut ity0, mut ity1, mut ity2] = intensities;
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+        // Sort the vertices by y-coordinate ascending (t0.y <= t1.y <= t2.y)
+        if t0.y > t1.y {
+            std::mem::swap(&mut t0, &mut t1);
+            std::mem::swap(&mut uv0, &mut uv1);
+            std::mem::swap(&mut ity0, &mut ity1);
+        }
+        if t0.y > t2.y {
+            std::mem::swap(&mut t0, &mut t2);
+            std::mem::swap(&mut uv0, &mut uv2);
+            std::mem::swap(&mut ity0, &mut ity2);
+        }
+        if t1.y > t2.y {
+            std::mem::swap(&mut t1, &mut t2);
+            std::mem::swap(&mut uv1, &mut uv2);
+            std::mem::swap(&mut ity1, &mut ity2);
+        }
+
+        let total_height = (t2.y - t0.y) as i32;
+        if total_height == 0 {
+            return Err(RenderError::DivisionByZero);
+        }
+
+        for i in 0..total_height {
+            let second_half = i as f32 > t1.y - t0.y || t1.y == t0.y;
+            let segment_height = if second_half {
+                t2.y - t1.y
+            } else {
+                t1.y - t0.y
+            };
+            let alpha = i as f32 / total_height as f32;
+            let beta = (i as f32 - if second_half { t1.y - t0.y } else { 0.0 }) / segment_height;
+            let mut p_a = t0.lerp(t2, alpha);
+            let mut p_b = if second_half {
+                t1.lerp(t2, beta)
+            } else {
+                t0.lerp(t1, beta)
+            };
+            let mut uvp_a = uv0.lerp(uv2, alpha);
+            let mut uvp_b = if second_half {
+                uv1.lerp(uv2, beta)
+            } else {
+                uv0.lerp(uv1, beta)
+            };
+            let mut ity_a = lerp(ity0, ity2, alpha);
+            let mut ity_b = if second_half {
+                lerp(ity1, ity2, beta)
+            } else {
+                lerp(ity0, ity1, beta)
+            };
+
+            if p_a.x > p_b.x {
+                std::mem::swap(&mut p_a, &mut p_b);
+                std::mem::swap(&mut uvp_a, &mut uvp_b);
+                std::mem::swap(&mut ity_a, &mut ity_b);
+            }
+
+            for j in (p_a.x as i32)..=(p_b.x as i32) {
+                let phi = if p_b.x as i32 == p_a.x as i32 {
+                    1.0
+                } else {
+                    (j as f32 - p_a.x) / (p_b.x - p_a.x)
+                };
+                let p_cur = p_a.lerp(p_b, phi);
+                let uvp_cur = uvp_a.lerp(uvp_b, phi);
+
+                let (x, y) = (p_cur.x as i32, p_cur.y as i32);
+                let Some(k) = self.depth_index(x, y) else {
+                    continue;
+                };
+                if !self.mask_allows(x, y) {
+                    continue;
+                }
+                if !self.nearer(p_cur.z, zbuffer[k]) {
+                    self.stats.depth_tests_failed += 1;
+                    continue;
+                }
+                let color = match self.sample_texture(texture_image, mipmaps, uvp_cur, lod) {
+                    Some(c) => c,
+                    None => {
+                        return Err(RenderError::TextureOutOfBounds {
+                            x: p_cur.x as i32,
+                            y: p_cur.y as i32,
+                        })
+                    }
+                };
+                if self.alpha_discards(texture_image, &color) {
+                    continue;
+                }
+                zbuffer[k] = p_cur.z;
+                self.stats.pixels_shaded += 1;
+                if let Some(ids) = self.id_buffer.as_mut() {
+                    ids[k] = face as i32;
+                }
+                let intensity = self.shadowed(lerp(ity_a, ity_b, phi), p_cur.x, p_cur.y, p_cur.z);
+                self.write_hdr(p_cur.x as i32, p_cur.y as i32, color, intensity, 1.0);
+                let color = self.light_texel(color, intensity);
+                self.image.set(p_cur.x as i32, p_cur.y as i32, &color);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bounding-box counterpart of `draw_triangle`. Every pixel center in the triangle's screen
+    /// rectangle is tested, and those inside it are drawn with depth, uv and intensity
+    /// interpolated from the barycentric weights. Centers lying exactly on an edge follow the
+    /// same top-left rule as `draw_triangle_shaded`. Thin triangles need no special case.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_triangle_barycentric(
+        &mut self,
+        t0: Vec3f,
+        t1: Vec3f,
+        t2: Vec3f,
+        uv0: Vec2f,
+        uv1: Vec2f,
+        uv2: Vec2f,
+        texture_image: &TGAImage,
+        mipmaps: &[TGAImage],
+        intensities: [f32; 3],
+        face: usize,
+        zbuffer: &mut [f32],
+    ) -> Result<(), RenderError> {
+        let area = 2.0 * Self::signed_area(t0, t1, t2);
+        if area.abs() < 2.0 * MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return Ok(());
+        }
+        let [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+        let lod = Self::uv_lod([t0, t1, t2], [uv0, uv1, uv2], texture_image);
+        let owned = Self::owned_edges([t0, t1, t2], area);
+
+        let x_min = (t0.x.min(t1.x).min(t2.x).floor() as i32).max(0);
+        let x_max = (t0.x.max(t1.x).max(t2.x).ceil() as i32).min(self.image.width - 1);
+        let y_min = (t0.y.min(t1.y).min(t2.y).floor() as i32).max(0);
+        let y_max = (t0.y.max(t1.y).max(t2.y).ceil() as i32).min(self.image.height - 1);
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: Vec3f, b: Vec3f| (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x);
+                let w = [
+                    edge(t1, t2) / area,
+                    edge(t2, t0) / area,
+                    edge(t0, t1) / area,
+                ];
+                let inside = w
+                    .iter()
+                    .zip(owned)
+                    .all(|(&w, owned)| w > 0.0 || (w == 0.0 && owned));
+                if !inside || !self.mask_allows(x, y) {
+                    continue;
+                }
+                let [w0, w1, w2] = w;
+                let z = t0.z * w0 + t1.z * w1 + t2.z * w2;
+                let uv = uv0 * w0 + uv1 * w1 + uv2 * w2;
+                let intensity = intensities[0] * w0 + intensities[1] * w1 + intensities[2] * w2;
+
+                let k = (x + y * self.image.width) as usize;
+                if !self.nearer(z, zbuffer[k]) {
+                    self.stats.depth_tests_failed += 1;
+                    continue;
+                }
+                let color = match self.sample_texture(texture_image, mipmaps, uv, lod) {
+                    Some(c) => c,
+                    None => return Err(RenderError::TextureOutOfBounds { x, y }),
+                };
+                if self.alpha_discards(texture_image, &color) {
+                    continue;
+                }
+                zbuffer[k] = z;
+                self.stats.pixels_shaded += 1;
+                if let Some(ids) = self.id_buffer.as_mut() {
+                    ids[k] = face as i32;
+                }
+
+                let intensity = self.shadowed(intensity, x as f32, y as f32, z);
+                self.write_hdr(x, y, color, intensity, 1.0);
+                let color = self.light_texel(color, intensity);
+                self.image.set(x, y, &color);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Anti-aliased counterpart of `draw_triangle`. Pixels are sampled at their centers and
+    /// partially covered edge pixels are blended over the framebuffer by their coverage, which
+    /// is estimated from the distance to the nearest edge. Only fully covered pixels write depth.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_triangle_aa(
+        &mut self,
+        t0: Vec3f,
+        t1: Vec3f,
+        t2: Vec3f,
+        uv0: Vec2f,
+        uv1: Vec2f,
+        uv2: Vec2f,
+        texture_image: &TGAImage,
+        mipmaps: &[TGAImage],
+        intensities: [f32; 3],
+        face: usize,
+        zbuffer: &mut [f32],
+    ) -> Result<(), RenderError> {
+        let area = 2.0 * Self::signed_area(t0, t1, t2);
+        if area.abs() < 2.0 * MIN_TRIANGLE_AREA {
+            // Degenerate or sub-pixel triangle
+            return Ok(());
+        }
+        let [t0, t1, t2] = self.offset_depth([t0, t1, t2]);
+        let edge_lengths = [(t2 - t1).norm(), (t0 - t2).norm(), (t1 - t0).norm()];
+        let lod = Self::uv_lod([t0, t1, t2], [uv0, uv1, uv2], texture_image);
+
+        let x_min = (t0.x.min(t1.x).min(t2.x).floor() as i32 - 1).max(0);
+        let x_max = (t0.x.max(t1.x).max(t2.x).ceil() as i32 + 1).min(self.image.width - 1);
+        let y_min = (t0.y.min(t1.y).min(t2.y).floor() as i32 - 1).max(0);
+        let y_max = (t0.y.max(t1.y).max(t2.y).ceil() as i32 + 1).min(self.image.height - 1);
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let edge = |a: Vec3f, b: Vec3f| (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x);
+                let weights = [
+                    edge(t1, t2) / area,
+                    edge(t2, t0) / area,
+                    edge(t0, t1) / area,
+                ];
+
+                // Signed distance in pixels to the closest edge, positive inside
+                let distance = (0..3)
+                    .map(|i| weights[i] * area.abs() / edge_lengths[i])
+                    .fold(f32::INFINITY, f32::min);
+                let coverage = (distance + 0.5).clamp(0.0, 1.0);
+                if coverage <= 0.0 {
+                    continue;
+                }
+
+                // Pixels straddling an edge interpolate attributes at the nearest inside point
+                let clamped = weights.map(|w| w.max(0.0));
+                let sum: f32 = clamped.iter().sum();
+                let [w0, w1, w2] = clamped.map(|w| w / sum);
+                let z = t0.z * w0 + t1.z * w1 + t2.z * w2;
+                let uv = uv0 * w0 + uv1 * w1 + uv2 * w2;
+                let intensity = intensities[0] * w0 + intensities[1] * w1 + intensities[2] * w2;
+
+                if !self.mask_allows(x, y) {
+                    continue;
+                }
+                let k = (x + y * self.image.width) as usize;
+                if !self.nearer(z, zbuffer[k]) {
+                    self.stats.depth_tests_failed += 1;
+                    continue;
+                }
+                let color = match self.sample_texture(texture_image, mipmaps, uv, lod) {
+                    Some(c) => c,
+                    None => return Err(RenderError::TextureOutOfBounds { x, y }),
+                };
+                if self.alpha_discards(texture_image, &color) {
+                    continue;
+                }
+                self.stats.pixels_shaded += 1;
+                if coverage >= 1.0 {
+                    zbuffer[k] = z;
+                    if let Some(ids) = self.id_buffer.as_mut() {
+                        ids[k] = face as i32;
+                    }
+                }
+
+                let intensity = self.shadowed(intensity, x as f32, y as f32, z);
+                self.write_hdr(x, y, color, intensity, coverage);
+                let color = self.light_texel(color, intensity);
+                self.image.blend(x, y, &color, coverage);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mip level for a triangle's texture mapping: log2 of the texels crossed per pixel step in
+    /// x or y, from the screen-space derivatives of its uvs. Affine uvs make these constant
+    /// across the triangle. Magnified textures give 0.
+    fn uv_lod(
+        [t0, t1, t2]: [Vec3f; 3],
+        [uv0, uv1, uv2]: [Vec2f; 3],
+        texture_image: &TGAImage,
+    ) -> f32 {
+        let (e1, e2) = (t1 - t0, t2 - t0);
+        let det = e1.x * e2.y - e1.y * e2.x;
+        if det == 0.0 {
+            return 0.0;
+        }
+        // Gradients of the barycentric weights of t1 and t2
+        let (dw1_dx, dw1_dy) = (e2.y / det, -e2.x / det);
+        let (dw2_dx, dw2_dy) = (-e1.y / det, e1.x / det);
+
+        let texels = |duv: Vec2f| {
+            (duv.x * texture_image.width as f32).hypot(duv.y * texture_image.height as f32)
+        };
+        let duv_dx = (uv1 - uv0) * dw1_dx + (uv2 - uv0) * dw2_dx;
+        let duv_dy = (uv1 - uv0) * dw1_dy + (uv2 - uv0) * dw2_dy;
+        texels(duv_dx).max(texels(duv_dy)).max(1.0).log2()
+    }
+
+    /// Scales a texel by the light intensity, in linear space if textures are sRGB.
+    fn light_texel(&self, texel: TGAColor, intensity: f32) -> TGAColor {
+        let [b, g, r, a] = texel.raw;
+        let shade = |c: u8| {
+            if self.texture_srgb {
+                linear_to_srgb(srgb_to_linear(c) * intensity)
+            } else {
+                clamp_u8(c as f32 * intensity)
+            }
+        };
+        TGAColor::rgba(shade(r), shade(g), shade(b), a)
+    }
+
+    /// Lights a texel like `light_texel` into the HDR framebuffer, if any, without clamping,
+    /// blending it over the stored value by `coverage`.
+    fn write_hdr(&mut self, x: i32, y: i32, texel: TGAColor, intensity: f32, coverage: f32) {
+        let srgb = self.texture_srgb;
+        let Some(pixel) = self.hdr.as_mut().and_then(|hdr| hdr.get_mut(x, y)) else {
+            return;
+        };
+        let [b, g, r, _] = texel.raw;
+        let decode = |c: u8| {
+            if srgb {
+                srgb_to_linear(c)
+            } else {
+                c as f32 / 255.0
+            }
+        };
+        for (dst, c) in pixel.iter_mut().zip([r, g, b]) {
+            *dst += (decode(c) * intensity - *dst) * coverage;
+        }
+    }
+
+    fn sample_texture(
+        &self,
+        texture_image: &TGAImage,
+        mipmaps: &[TGAImage],
+        mut uv: Vec2f,
+        lod: f32,
+    ) -> Option<TGAColor> {
+        let inside = (0.0..=1.0).contains(&uv.x) && (0.0..=1.0).contains(&uv.y);
+        match self.missing_texel {
+            MissingTexel::Error => {}
+            MissingTexel::Fill(c) if !inside => return Some(c),
+            MissingTexel::Fill(c) => {
+                let color = self.sample_texture_at(texture_image, mipmaps, uv, lod);
+                return color.or(Some(c));
+            }
+            MissingTexel::Clamp => uv = Vec2f::new(uv.x.clamp(0.0, 1.0), uv.y.clamp(0.0, 1.0)),
+        }
+        self.sample_texture_at(texture_image, mipmaps, uv, lod)
+    }
+
+    fn sample_texture_at(
+        &self,
+        texture_image: &TGAImage,
+        mipmaps: &[TGAImage],
+        uv: Vec2f,
+        lod: f32,
+    ) -> Option<TGAColor> {
+        let wrap = |t: f32| match self.wrap_mode {
+            WrapMode::Clamp => t.clamp(0.0, 1.0),
+            WrapMode::Repeat => t.rem_euclid(1.0),
+        };
+        let uv = Vec2f::new(wrap(uv.x), wrap(uv.y));
+        match self.texture_filter {
+            // uv == 1.0 lands one texel past the edge, so clamp onto the last texel
+            TextureFilter::Nearest => texture_image.get(
+                ((uv.x * texture_image.width as f32) as i32).min(texture_image.width - 1),
+                ((uv.y * texture_image.height as f32) as i32).min(texture_image.height - 1),
+            ),
+            TextureFilter::Trilinear => self.sample_trilinear(mipmaps, uv.x, uv.y, lod),
+        }
+    }
+
+    /// Samples a mip pyramid from `TGAImage::generate_mipmaps`, bilinearly filtering the two
+    /// levels around `lod` and blending between them.
+    #[allow(dead_code)]
+    pub fn sample_trilinear(
+        &self,
+        pyramid: &[TGAImage],
+        u: f32,
+        v: f32,
+        lod: f32,
+    ) -> Option<TGAColor> {
+        let last = pyramid.len().checked_sub(1)?;
+        let lod = lod.clamp(0.0, last as f32);
+        let level0 = lod.floor() as usize;
+        let level1 = (level0 + 1).min(last);
+        let t = lod - level0 as f32;
+
+        let c0 = Self::sample_bilinear(&pyramid[level0], u, v)?;
+        let c1 = Self::sample_bilinear(&pyramid[level1], u, v)?;
+        let mut raw = [0u8; 4];
+        for i in 0..4 {
+            raw[i] = clamp_u8((c0[i] + (c1[i] - c0[i]) * t).round());
+        }
+        Some(TGAColor {
+            raw,
+            bytespp: pyramid[level0].bytespp,
+        })
+    }
+
+    fn sample_bilinear(image: &TGAImage, u: f32, v: f32) -> Option<[f32; 4]> {
+        // Texel centers sit at half-integer coordinates
+        let x = (u * image.width as f32 - 0.5).clamp(0.0, (image.width - 1) as f32);
+        let y = (v * image.height as f32 - 0.5).clamp(0.0, (image.height - 1) as f32);
+        let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+        let (x1, y1) = (
+            (x0 + 1).min(image.width - 1),
+            (y0 + 1).min(image.height - 1),
+        );
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+        let c00 = image.get(x0, y0)?.raw;
+        let c10 = image.get(x1, y0)?.raw;
+        let c01 = image.get(x0, y1)?.raw;
+        let c11 = image.get(x1, y1)?.raw;
+        let mut res = [0.0f32; 4];
+        for i in 0..4 {
+            let top = c00[i] as f32 + (c10[i] as f32 - c00[i] as f32) * fx;
+            let bottom = c01[i] as f32 + (c11[i] as f32 - c01[i] as f32) * fx;
+            res[i] = top + (bottom - top) * fy;
+        }
+        Some(res)
+    }
+
+    /// Draws a 1px line from `t0` to `t1`, both endpoints included. Pixels outside the image are
+    /// skipped, so lines may extend past the canvas; only the part clipped to the image is
+    /// iterated.
+    ///
+    /// With the integer levels (Level2, the default, and Level3) the result is exact: one pixel
+    /// is set per step along the major axis, and its minor coordinate is the true line position
+    /// rounded to the nearest pixel, with exact halves rounded towards the endpoint that has the
+    /// smaller major coordinate.
+    #[allow(dead_code)]
+    pub fn draw_line(
+        &mut self,
+        t0: Vec2i,
+        t1: Vec2i,
+        color: &TGAColor,
+        optimization_level: OptimizationLevel,
+    ) {
+        let image = &mut self.image;
+        let mut steep = false;
+        let mut x0 = t0.x;
+        let mut x1 = t1.x;
+        let mut y0 = t0.y;
+        let mut y1 = t1.y;
+
+        if (x0 - x1).abs() < (y0 - y1).abs() {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+            steep = true;
+        }
+
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        // Extents along the major (x) and minor (y) axes after the steep swap
+        let (major_len, minor_len) = if steep {
+            (image.height, image.width)
+        } else {
+            (image.width, image.height)
+        };
+        let in_bounds = x0 >= 0 && x1 < major_len && y0.min(y1) >= 0 && y0.max(y1) < minor_len;
+        let Some(visible) = Self::visible_major_range(x0, y0, x1, y1, major_len, minor_len) else {
+            return;
+        };
+
+        match optimization_level {
+            OptimizationLevel::Level3 if in_bounds => {
+                let bytespp = image.bytespp;
+                let row = image.width as usize * bytespp;
+                let (major_stride, minor_stride) = if steep {
+                    (row, bytespp)
+                } else {
+                    (bytespp, row)
+                };
+                let minor_step = if y1 > y0 {
+                    minor_stride as isize
+                } else {
+                    -(minor_stride as isize)
+                };
+                let pixel = &color.raw[..bytespp];
+
+                let dx = x1 - x0;
+                let derror = (y1 - y0).abs() * 2;
+                let mut error = 0;
+                let mut idx = (x0 as usize * major_stride + y0 as usize * minor_stride) as isize;
+                for _ in x0..=x1 {
+                    let i = idx as usize;
+                    image.data[i..i + bytespp].copy_from_slice(pixel);
+                    error += derror;
+                    if error > dx {
+                        idx += minor_step;
+                        error -= dx * 2;
+                    }
+                    idx += major_stride as isize;
+                }
+            }
+            OptimizationLevel::Level0 => {
+                for x in visible {
+                    let t = (x - x0) as f32 / (x1 - x0) as f32;
+                    let y = y0 as f32 + t * (y1 - y0) as f32;
+                    if steep {
+                        image.set(y as i32, x, color);
+                    } else {
+                        image.set(x, y as i32, color);
+                    }
+                }
+            }
+            OptimizationLevel::Level1 => {
+                let dx = x1 - x0;
+                let dy = y1 - y0;
+                let derror = dy as f32 / dx as f32;
+                // Skip ahead to the first visible column: after k steps y has moved once for
+                // every whole step k * derror passed beyond 0.5
+                let k = (*visible.start() - x0) as f32;
+                let steps = ((k * derror + 0.5).ceil() - 1.0).max(0.0);
+                let mut error = k * derror - steps;
+                let mut y = y0 + steps as i32 * if y1 > y0 { 1 } else { -1 };
+                for x in visible {
+                    if steep {
+                        image.set(y, x, color);
+                    } else {
+                        image.set(x, y, color);
+                    }
+                    error += derror;
+                    if error > 0.5 {
+                        y += if y1 > y0 { 1 } else { -1 };
+                        error -= 1.0;
+                    }
+                }
+            }
+            OptimizationLevel::Level2 | OptimizationLevel::Level3 => {
+                let dx = x1 - x0;
+                let dy = y1 - y0;
+                let derror = dy.abs() * 2;
+                // Skip ahead to the first visible column: the error stays in (-dx, dx], so after
+                // k steps y has moved by the fewest 2 * dx decrements that bring k * derror there
+                let k = (*visible.start() - x0) as i64;
+                let (dx2, total) = (2 * dx as i64, k * derror as i64);
+                let steps = if dx == 0 {
+                    0
+                } else {
+                    (total - dx as i64 + dx2 - 1).div_euclid(dx2).max(0)
+                };
+                let mut error = (total - steps * dx2) as i32;
+                let mut y = y0 + steps as i32 * if y1 > y0 { 1 } else { -1 };
+                for x in visible {
+                    if steep {
+                        image.set(y, x, color);
+                    } else {
+                        image.set(x, y, color);
+                    }
+                    error += derror;
+                    if error > dx {
+                        y += if y1 > y0 { 1 } else { -1 };
+                        error -= dx * 2;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Columns along the major axis of a line, ordered and swapped as in `draw_line`, that can
+    /// set pixels in a `major_len`x`minor_len` image. The segment is clipped to the image with
+    /// Cohen-Sutherland, widened by a pixel across for rounding and by a column either way for
+    /// the clipping error; `None` if it misses the image.
+    fn visible_major_range(
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        major_len: i32,
+        minor_len: i32,
+    ) -> Option<std::ops::RangeInclusive<i32>> {
+        const LEFT: u8 = 1;
+        const RIGHT: u8 = 2;
+        const BOTTOM: u8 = 4;
+        const TOP: u8 = 8;
+        let (x_min, x_max) = (0.0, (major_len - 1) as f32);
+        let (y_min, y_max) = (-1.0, minor_len as f32);
+        let outcode = |(x, y): (f32, f32)| {
+            let mut code = 0;
+            if x < x_min {
+                code |= LEFT;
+            } else if x > x_max {
+                code |= RIGHT;
+            }
+            if y < y_min {
+                code |= BOTTOM;
+            } else if y > y_max {
+                code |= TOP;
+            }
+            code
+        };
+
+        let mut p0 = (x0 as f32, y0 as f32);
+        let mut p1 = (x1 as f32, y1 as f32);
+        let (mut code0, mut code1) = (outcode(p0), outcode(p1));
+        // Each endpoint is moved onto at most two edges; rounding error is covered by the margin
+        for _ in 0..4 {
+            if code0 | code1 == 0 {
+                break;
+            }
+            if code0 & code1 != 0 {
+                return None;
+            }
+            let code = if code0 != 0 { code0 } else { code1 };
+            let ((ax, ay), (bx, by)) = (p0, p1);
+            let p = if code & (LEFT | RIGHT) != 0 {
+                let x = if code & LEFT != 0 { x_min } else { x_max };
+                (x, ay + (by - ay) * (x - ax) / (bx - ax))
+            } else {
+                let y = if code & BOTTOM != 0 { y_min } else { y_max };
+                (ax + (bx - ax) * (y - ay) / (by - ay), y)
+            };
+            if code == code0 {
+                p0 = p;
+                code0 = outcode(p0);
+            } else {
+                p1 = p;
+                code1 = outcode(p1);
+            }
+        }
+
+        let start = (p0.0.min(p1.0).floor() as i32 - 1).max(x0).max(0);
+        let end = (p0.0.max(p1.0).ceil() as i32 + 1)
+            .min(x1)
+            .min(major_len - 1);
+        (start <= end).then_some(start..=end)
+    }
+
+    /// Draws a line `width` pixels wide with flat (butt) ends.
+    #[allow(dead_code)]
+    pub fn draw_line_thick(&mut self, t0: Vec2i, t1: Vec2i, width: f32, color: &TGAColor) {
+        let half = width.max(1.0) / 2.0;
+        let r = half.ceil() as i32;
+        let (x0, y0) = (t0.x as f32, t0.y as f32);
+        let dx = (t1.x - t0.x) as f32;
+        let dy = (t1.y - t0.y) as f32;
+        let len2 = dx * dx + dy * dy;
+
+        for y in (t0.y.min(t1.y) - r).max(0)..=(t0.y.max(t1.y) + r).min(self.image.height - 1) {
+            for x in (t0.x.min(t1.x) - r).max(0)..=(t0.x.max(t1.x) + r).min(self.image.width - 1) {
+                let px = x as f32 - x0;
+                let py = y as f32 - y0;
+                let dist = if len2 == 0.0 {
+                    (px * px + py * py).sqrt()
+                } else {
+                    // Projection onto the segment, in units of its length
+                    let t = (px * dx + py * dy) / len2;
+                    if !(0.0..=1.0).contains(&t) {
+                        continue;
+                    }
+                    (px * dy - py * dx).abs() / len2.sqrt()
+                };
+                if dist <= half {
+                    self.image.set(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Draws an anti-aliased line with Xiaolin Wu's algorithm. Pixel coverage is blended
+    /// against the existing framebuffer color.
+    #[allow(dead_code)]
+    pub fn draw_line_aa(&mut self, t0: Vec2i, t1: Vec2i, color: &TGAColor) {
+        let fpart = |v: f32| v - v.floor();
+        let rfpart = |v: f32| 1.0 - fpart(v);
+
+        let mut x0 = t0.x as f32;
+        let mut y0 = t0.y as f32;
+        let mut x1 = t1.x as f32;
+        let mut y1 = t1.y as f32;
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let image = &mut self.image;
+        let mut plot = |x: i32, y: i32, coverage: f32| {
+            if steep {
+                image.blend(y, x, color, coverage);
+            } else {
+                image.blend(x, y, color, coverage);
+            }
+        };
+
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+        let gradient = if dx == 0.0 { 1.0 } else { dy / dx };
+
+        // First endpoint
+        let xend = x0.round();
+        let yend = y0 + gradient * (xend - x0);
+        let xgap = rfpart(x0 + 0.5);
+        let xpxl1 = xend as i32;
+        let ypxl1 = yend.floor() as i32;
+        plot(xpxl1, ypxl1, rfpart(yend) * xgap);
+        plot(xpxl1, ypxl1 + 1, fpart(yend) * xgap);
+        let mut intery = yend + gradient;
+
+        // Second endpoint
+        let xend = x1.round();
+        let yend = y1 + gradient * (xend - x1);
+        let xgap = fpart(x1 + 0.5);
+        let xpxl2 = xend as i32;
+        let ypxl2 = yend.floor() as i32;
+        plot(xpxl2, ypxl2, rfpart(yend) * xgap);
+        plot(xpxl2, ypxl2 + 1, fpart(yend) * xgap);
+
+        for x in (xpxl1 + 1)..xpxl2 {
+            plot(x, intery.floor() as i32, rfpart(intery));
+            plot(x, intery.floor() as i32 + 1, fpart(intery));
+            intery += gradient;
+        }
+    }
+
+    /// Draws an axis-aligned rectangle whose corner with the smallest coordinates is `top_left`.
+    #[allow(dead_code)]
+    pub fn draw_rect(&mut self, top_left: Vec2i, w: i32, h: i32, color: &TGAColor, filled: bool) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        let x0 = top_left.x;
+        let y0 = top_left.y;
+        let x1 = top_left.x + w - 1;
+        let y1 = top_left.y + h - 1;
+
+        if filled {
+            for y in y0..=y1 {
+                self.draw_hline(x0, x1, y, color);
+            }
+        } else {
+            self.draw_hline(x0, x1, y0, color);
+            self.draw_hline(x0, x1, y1, color);
+            for y in y0..=y1 {
+                self.image.set(x0, y, color);
+                self.image.set(x1, y, color);
+            }
+        }
+    }
+
+    /// Draws a circle with the midpoint circle algorithm.
+    #[allow(dead_code)]
+    pub fn draw_circle(&mut self, center: Vec2i, radius: i32, color: &TGAColor, filled: bool) {
+        if radius < 0 {
+            return;
+        }
+        let (cx, cy) = (center.x, center.y);
+        let mut x = radius;
+        let mut y = 0;
+        let mut err = 1 - radius;
+
+        while x >= y {
+            if filled {
+                self.draw_hline(cx - x, cx + x, cy + y, color);
+                self.draw_hline(cx - x, cx + x, cy - y, color);
+                self.draw_hline(cx - y, cx + y, cy + x, color);
+                self.draw_hline(cx - y, cx + y, cy - x, color);
+            } else {
+                for (px, py) in [
+                    (x, y),
+                    (y, x),
+                    (-y, x),
+                    (-x, y),
+                    (-x, -y),
+                    (-y, -x),
+                    (y, -x),
+                    (x, -y),
+                ] {
+                    self.image.set(cx + px, cy + py, color);
+                }
+            }
+
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Draws a horizontal span, clipped to the image bounds.
+    fn draw_hline(&mut self, x0: i32, x1: i32, y: i32, color: &TGAColor) {
+        if y < 0 || y >= self.image.height {
+            return;
+        }
+        for x in x0.max(0)..=x1.min(self.image.width - 1) {
+            self.image.set(x, y, color);
+        }
+    }
+}
+
+/// Fluent configuration for a `Renderer`. Settings left out keep the defaults of
+/// `Renderer::new`, and without any `light` call the default light is used.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct RendererBuilder {
+    width: i32,
+    height: i32,
+    shading: ShadingMode,
+    cull_mode: CullMode,
+    front_face: Winding,
+    ambient: f32,
+    lights: Vec<Vec3f>,
+    gamma: f32,
+}
+
+#[allow(dead_code)]
+impl RendererBuilder {
+    pub fn new() -> Self {
+        RendererBuilder {
+            width: 800,
+            height: 800,
+            shading: ShadingMode::Flat,
+            cull_mode: CullMode::Back,
+            front_face: Winding::CounterClockwise,
+            ambient: 0.0,
+            lights: Vec::new(),
+            gamma: 1.0,
+        }
+    }
+
+    pub fn width(mut self, width: i32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: i32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn shading(mut self, shading: ShadingMode) -> Self {
+        self.shading = shading;
+        self
+    }
+
+    /// Culls back faces, or no faces when `enabled` is false.
+    pub fn cull_backfaces(mut self, enabled: bool) -> Self {
+        self.cull_mode = if enabled {
+            CullMode::Back
+        } else {
+            CullMode::None
+        };
+        self
+    }
+
+    pub fn cull_mode(mut self, mode: CullMode) -> Self {
+        self.cull_mode = mode;
+        self
+    }
+
+    /// Screen-space winding of the faces back-face culling keeps.
+    pub fn front_face(mut self, winding: Winding) -> Self {
+        self.front_face = winding;
+        self
+    }
+
+    /// Intensity added to every lit fragment regardless of its orientation.
+    pub fn ambient(mut self, ambient: f32) -> Self {
+        self.ambient = ambient;
+        self
+    }
+
+    /// Adds a directional light shining along `dir`. Can be called repeatedly; the intensities
+    /// of all lights add up.
+    pub fn light(mut self, dir: Vec3f) -> Self {
+        self.lights.push(dir.normalize(1.0));
+        self
+    }
+
+    /// Gamma applied by `save_tga_image`.
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn build(self) -> Renderer {
+        let mut renderer = Renderer::new(self.width, self.height);
+        renderer.shading = self.shading;
+        renderer.cull_mode = self.cull_mode;
+        renderer.front_face = self.front_face;
+        renderer.ambient = self.ambient;
+        if !self.lights.is_empty() {
+            renderer.lights = self.lights;
+        }
+        renderer.gamma = self.gamma;
+        renderer
+    }
+}
+
+impl Default for RendererBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    #[test]
+    fn test_draw_line() {
+        let width = 10;
+        let height = 5;
+        let c = &TGAColor::rgba(128, 1, 255, 255);
+
+        struct TestCase<'a> {
+            optimization_level: OptimizationLevel,
+            filename: &'a str,
+        }
+        for test in [
+            TestCase {
+                optimization_level: OptimizationLevel::Level0,
+                filename: "tests/images/line0.tga",
+            },
+            TestCase {
+                optimization_level: OptimizationLevel::Level1,
+                filename: "tests/images/line1.tga",
+            },
+            TestCase {
                 optimization_level: OptimizationLevel::Level2,
                 filename: "tests/images/line2.tga",
             },
+            TestCase {
+                optimization_level: OptimizationLevel::Level3,
+                filename: "tests/images/line2.tga",
+            },
+        ] {
+            let mut renderer = Renderer::new(width, height);
+            renderer.draw_line(
+                Vec2i::new(0, 0),
+                Vec2i::new(8, 5),
+                c,
+                test.optimization_level,
+            );
+
+            let mut testimage = TGAImage::new(width, height, Format::RGB);
+            testimage.read_tga_file(test.filename).unwrap();
+            testimage.flip_vertically();
+
+            assert_eq!(renderer.image.data, testimage.data);
+        }
+    }
+
+    fn count_set_pixels(image: &TGAImage) -> usize {
+        image
+            .data
+            .chunks(image.bytespp)
+            .filter(|p| p.iter().any(|&b| b != 0))
+            .count()
+    }
+
+    #[test]
+    fn test_draw_rect() {
+        let c = &TGAColor::rgba(255, 255, 255, 255);
+
+        let mut renderer = Renderer::new(10, 10);
+        renderer.draw_rect(Vec2i::new(2, 3), 3, 3, c, true);
+        assert_eq!(count_set_pixels(&renderer.image), 9);
+
+        // Partially off-canvas rectangles are clipped.
+        let mut renderer = Renderer::new(10, 10);
+        renderer.draw_rect(Vec2i::new(-1, -1), 3, 3, c, true);
+        assert_eq!(count_set_pixels(&renderer.image), 4);
+    }
+
+    #[test]
+    fn test_draw_circle() {
+        let c = &TGAColor::rgba(255, 255, 255, 255);
+        let mut renderer = Renderer::new(9, 9);
+        renderer.draw_circle(Vec2i::new(4, 4), 2, c, false);
+
+        let mut expected = Vec::new();
+        for (dx, dy) in [
+            (2, 0),
+            (-2, 0),
+            (0, 2),
+            (0, -2),
+            (2, 1),
+            (2, -1),
+            (-2, 1),
+            (-2, -1),
+            (1, 2),
+            (-1, 2),
+            (1, -2),
+            (-1, -2),
+        ] {
+            expected.push((4 + dx, 4 + dy));
+        }
+        for y in 0..9 {
+            for x in 0..9 {
+                let is_set = renderer.image.get(x, y).unwrap().raw[0] != 0;
+                assert_eq!(is_set, expected.contains(&(x, y)), "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_line_thick() {
+        let c = &TGAColor::rgba(255, 255, 255, 255);
+
+        let mut thin = Renderer::new(40, 40);
+        thin.draw_line(
+            Vec2i::new(5, 8),
+            Vec2i::new(30, 20),
+            c,
+            OptimizationLevel::Level2,
+        );
+        let mut thick = Renderer::new(40, 40);
+        thick.draw_line_thick(Vec2i::new(5, 8), Vec2i::new(30, 20), 3.0, c);
+
+        let ratio = count_set_pixels(&thick.image) as f32 / count_set_pixels(&thin.image) as f32;
+        assert!((2.5..=3.5).contains(&ratio), "ratio: {}", ratio);
+    }
+
+    #[test]
+    fn test_draw_line_aa() {
+        let c = &TGAColor::rgba(255, 255, 255, 255);
+        let mut renderer = Renderer::new(10, 3);
+        renderer.draw_line_aa(Vec2i::new(1, 1), Vec2i::new(8, 1), c);
+
+        let end = renderer.image.get(1, 1).unwrap().raw[0];
+        let middle = renderer.image.get(4, 1).unwrap().raw[0];
+        assert!(end > 0 && end < 255, "end: {}", end);
+        assert_eq!(middle, 255);
+    }
+
+    #[test]
+    fn test_depth_precision() {
+        // Two triangles 1e-5 apart in z; the second one is nearer and must win even though
+        // it is drawn last.
+        let model = Model::new("tests/models/coplanar.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 255));
+
+        let mut renderer = Renderer::new(10, 10);
+        renderer.set_depth_range(1.0e6);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        let [b, g, r, _] = renderer.image.get(3, 3).unwrap().raw;
+        assert_eq!((r, g, b), (0, 0, 255));
+    }
+
+    #[test]
+    fn test_render_turntable() {
+        // Off-center and asymmetric, so every quarter turn shows a different silhouette
+        let model = Model::new("tests/models/sample.obj").expect("Failed to load model.");
+        let dir = std::env::temp_dir().join("tinyrenderer_test_render_turntable");
+        let mut renderer = Renderer::new(32, 32);
+        renderer
+            .render_turntable(&model, 4, &dir.to_string_lossy())
+            .unwrap();
+
+        let frames: Vec<Vec<u8>> = (0..4)
+            .map(|i| std::fs::read(dir.join(format!("frame_{:03}.tga", i))).unwrap())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            assert!(!frame.is_empty());
+            assert!(frames[..i].iter().all(|other| other != frame));
+        }
+    }
+
+    #[test]
+    fn test_camera_path() {
+        let keyframes = vec![
+            (Vec3f::new(3.0, 1.0, 3.0), Vec3f::new(0.0, 0.0, 0.0)),
+            (Vec3f::new(4.0, 2.0, 0.0), Vec3f::new(0.0, 0.5, 0.0)),
+            (Vec3f::new(3.0, 3.0, -3.0), Vec3f::new(0.0, 1.0, 0.0)),
+            (Vec3f::new(0.0, 4.0, -4.0), Vec3f::new(0.0, 1.5, 0.0)),
+        ];
+        let path = CameraPath::new(keyframes.clone());
+        let same = |a: Vec3f, b: Vec3f| (a.x, a.y, a.z) == (b.x, b.y, b.z);
+        for (i, &(eye, center)) in keyframes.iter().enumerate() {
+            let (e, c) = path.sample(i as f32 / 3.0);
+            assert!(same(e, eye) && same(c, center), "keyframe {}", i);
+        }
+
+        for i in 0..3 {
+            let (e, c) = path.sample((i as f32 + 0.5) / 3.0);
+            let ((e0, c0), (e1, c1)) = (keyframes[i], keyframes[i + 1]);
+            let between = |v: f32, a: f32, b: f32| a.min(b) <= v && v <= a.max(b);
+            assert!(
+                between(e.y, e0.y, e1.y) && between(e.z, e0.z, e1.z),
+                "eye {}",
+                e
+            );
+            assert!(between(c.y, c0.y, c1.y), "center {}", c);
+        }
+    }
+
+    #[test]
+    fn test_render_model_toon() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        // Half intensity falls in the second of three bands
+        let mut renderer = Renderer::builder()
+            .width(20)
+            .height(20)
+            .light(Vec3f::new(0.866, 0.0, -0.5))
+            .build();
+        renderer.render_model_toon(&model, 3).unwrap();
+        assert_eq!(renderer.image.get(5, 5).unwrap().raw[..3], [170, 170, 170]);
+        // Outlined inside the hypotenuse, but not along the image border
+        assert_eq!(renderer.image.get(9, 10).unwrap().raw[..3], [0, 0, 0]);
+        assert_eq!(renderer.image.get(8, 9).unwrap().raw[..3], [170, 170, 170]);
+        assert_eq!(renderer.image.get(0, 5).unwrap().raw[..3], [170, 170, 170]);
+    }
+
+    #[test]
+    fn test_camera_aspect_ratio() {
+        let model = Model::new("tests/models/cube.obj").expect("Failed to load model.");
+        // Extents in pixels of everything drawn, as (width, height)
+        let extent = |w: i32, h: i32| {
+            let mut renderer = Renderer::new(w, h);
+            renderer
+                .render_model_with_camera(
+                    &model,
+                    CameraMode::Perspective {
+                        fov: std::f32::consts::FRAC_PI_6,
+                        near: 0.1,
+                        far: 100.0,
+                    },
+                )
+                .unwrap();
+            let drawn: Vec<(i32, i32)> = (0..h)
+                .flat_map(|y| (0..w).map(move |x| (x, y)))
+                .filter(|&(x, y)| renderer.image.get(x, y).unwrap().raw[..3] != [0, 0, 0])
+                .collect();
+            let span = |coord: fn(&(i32, i32)) -> i32| {
+                drawn.iter().map(coord).max().unwrap() - drawn.iter().map(coord).min().unwrap()
+            };
+            (span(|p| p.0), span(|p| p.1))
+        };
+
+        // Doubling the width widens the view instead of stretching the model
+        let (square_w, square_h) = extent(80, 80);
+        let (wide_w, wide_h) = extent(160, 80);
+        assert!(square_w > 0 && square_h > 0);
+        assert!((wide_w - square_w).abs() <= 1, "{} vs {}", wide_w, square_w);
+        assert!((wide_h - square_h).abs() <= 1, "{} vs {}", wide_h, square_h);
+    }
+
+    #[test]
+    fn test_orthographic_camera() {
+        // Every vertex normal faces the light, so the whole cube silhouette is lit.
+        let model = Model::new("tests/models/cube.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(64, 64);
+        renderer.set_camera(
+            Vec3f::new(0.0, 3.0, 1.0),
+            Vec3f::new(0.0, 0.0, 0.0),
+            Vec3f::new(0.0, 1.0, 0.0),
+        );
+        renderer
+            .render_model_with_camera(
+                &model,
+                CameraMode::Orthographic {
+                    size: 1.0,
+                    near: 0.1,
+                    far: 10.0,
+                },
+            )
+            .unwrap();
+
+        // The cube's x-aligned edges stay vertical, so every row spans the same columns.
+        let mut spans = Vec::new();
+        for y in 0..renderer.image.height {
+            let lit: Vec<i32> = (0..renderer.image.width)
+                .filter(|&x| renderer.image.get(x, y).unwrap().raw[0] != 0)
+                .collect();
+            if let (Some(&first), Some(&last)) = (lit.first(), lit.last()) {
+                spans.push((first, last));
+            }
+        }
+        assert!(spans.len() > 10);
+        assert!(spans.iter().all(|&span| span == spans[0]), "{:?}", spans);
+    }
+
+    #[test]
+    fn test_render_model_hidden_line() {
+        let model = Model::new("tests/models/overlap.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 255));
+        let edge = TGAColor::rgba(0, 255, 0, 255);
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer
+            .render_model_hidden_line(&model, &texture, &edge)
+            .unwrap();
+
+        let rgb = |x, y| {
+            let [b, g, r, _] = renderer.image.get(x, y).unwrap().raw;
+            (r, g, b)
+        };
+        // The far triangle's bottom edge runs behind the near triangle.
+        assert_eq!(rgb(8, 5), (255, 0, 0));
+        // Its diagonal edge and the near triangle's diagonal are in plain view.
+        assert_eq!(rgb(12, 13), (0, 255, 0));
+        assert_eq!(rgb(10, 10), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_render_model_face_ids() {
+        // Sample the near triangle and the part of the far one it does not cover.
+        let model = Model::new("tests/models/overlap.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_face_ids(&model).unwrap();
+
+        let near = renderer.image.get(2, 2).unwrap().raw;
+        let far = renderer.image.get(15, 8).unwrap().raw;
+        assert_ne!(near, [0, 0, 0, 0]);
+        assert_ne!(far, [0, 0, 0, 0]);
+        assert_ne!(near, far);
+
+        // Both halves of an untriangulated quad get the quad's color
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_face_ids(&model).unwrap();
+        let color = Renderer::face_id_color(0).raw;
+        assert_eq!(renderer.image.get(15, 3).unwrap().raw[..3], color[..3]);
+        assert_eq!(renderer.image.get(3, 15).unwrap().raw[..3], color[..3]);
+    }
+
+    #[test]
+    fn test_render_points() {
+        let model = Model::new("tests/models/points.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer
+            .render_points(&model, &TGAColor::rgba(255, 255, 255, 255), 3)
+            .unwrap();
+
+        // Vertices land on (5, 5), (15, 5) and (10, 15), each covering a 3x3 square around it.
+        let lit: Vec<(i32, i32)> = (0..20)
+            .flat_map(|y| (0..20).map(move |x| (x, y)))
+            .filter(|&(x, y)| renderer.image.get(x, y).unwrap().raw[0] != 0)
+            .collect();
+        assert_eq!(lit.len(), 27);
+        for (cx, cy) in [(5, 5), (15, 5), (10, 15)] {
+            for (dx, dy) in [(-1, -1), (0, 0), (1, 1), (1, -1)] {
+                assert!(lit.contains(&(cx + dx, cy + dy)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_picking(true);
+        renderer.render_model_face_ids(&model).unwrap();
+        assert_ne!(renderer.image.get(2, 2).unwrap().raw, [0, 0, 0, 0]);
+
+        renderer.resize(30, 10);
+        assert_eq!((renderer.width(), renderer.height()), (30, 10));
+        assert_eq!((renderer.image.width, renderer.image.height), (30, 10));
+        assert_eq!(renderer.image.format(), Some(Format::RGB));
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw, [0, 0, 0, 0]);
+        assert_eq!(renderer.pick(2, 2), None);
+
+        // Buffers follow the new, non-square size
+        renderer.render_model_face_ids(&model).unwrap();
+        assert_ne!(renderer.image.get(2, 2).unwrap().raw, [0, 0, 0, 0]);
+        assert_eq!(renderer.pick(2, 2), Some(0));
+    }
+
+    #[test]
+    fn test_pick() {
+        let model = Model::new("tests/models/overlap.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_picking(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        assert_eq!(renderer.pick(2, 2), Some(0));
+        // Covered by both triangles; the near one wins the depth test.
+        assert_eq!(renderer.pick(8, 6), Some(0));
+        assert_eq!(renderer.pick(15, 8), Some(1));
+        assert_eq!(renderer.pick(19, 19), None);
+        assert_eq!(renderer.pick(-1, 0), None);
+
+        // Nothing is drawn yet
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_picking(true);
+        assert_eq!(renderer.pick(1, 1), None);
+    }
+
+    #[test]
+    fn test_edge_antialiasing() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_edge_antialiasing(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        let value = |x, y| renderer.image.get(x, y).unwrap().raw[0];
+        assert_eq!(value(5, 5), 255);
+        assert_eq!(value(15, 15), 0);
+        // The hypotenuse x + y = 20 passes through this pixel's center.
+        let edge = value(9, 10);
+        assert!(edge > 0 && edge < 255, "edge: {}", edge);
+    }
+
+    #[test]
+    fn test_render_progress() {
+        let model = Model::new("tests/models/overlap.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let reported = std::cell::RefCell::new(Vec::new());
+        let mut renderer = Renderer::new(20, 20);
+        renderer
+            .render_model(
+                &model,
+                &texture,
+                Some(&|fraction| reported.borrow_mut().push(fraction)),
+            )
+            .unwrap();
+
+        let reported = reported.into_inner();
+        assert!(!reported.is_empty());
+        assert!(reported.windows(2).all(|w| w[0] <= w[1]));
+        assert!((reported.last().unwrap() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_barycentric_rasterizer() {
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 255));
+        let (left, right) = (Vec2f::new(0.0, 0.0), Vec2f::new(1.0, 0.0));
+        let draw = |renderer: &mut Renderer, [t0, t1, t2]: [Vec3f; 3], zbuffer: &mut [f32]| {
+            renderer.draw_triangle_barycentric(
+                t0,
+                t1,
+                t2,
+                left,
+                right,
+                left,
+                &texture,
+                &[],
+                [1.0; 3],
+                0,
+                zbuffer,
+            )
+        };
+
+        let mut renderer = Renderer::new(20, 20);
+        let mut zbuffer = renderer.new_zbuffer();
+        let triangle = [
+            Vec3f::new(0.0, 0.0, 0.0),
+            Vec3f::new(19.0, 0.0, 10.0),
+            Vec3f::new(0.0, 19.0, 0.0),
+        ];
+        draw(&mut renderer, triangle, &mut zbuffer).unwrap();
+        // uv and depth follow the weights: u and z grow towards the second vertex
+        assert_eq!(renderer.image.get(1, 1).unwrap().raw[..3], [0, 0, 255]);
+        assert_eq!(renderer.image.get(16, 1).unwrap().raw[..3], [255, 0, 0]);
+        assert!(zbuffer[16 + 20] > zbuffer[1 + 20]);
+        // Centers outside the hypotenuse are left alone
+        assert_eq!(renderer.image.get(12, 12).unwrap().raw[..3], [0, 0, 0]);
+        assert_eq!(zbuffer[12 + 12 * 20], 1000.0);
+
+        // A sliver less than a scanline tall, which the scanline fill cannot interpolate
+        let sliver = [
+            Vec3f::new(0.0, 5.0, 0.0),
+            Vec3f::new(19.0, 5.0, 0.0),
+            Vec3f::new(19.0, 5.8, 0.0),
+        ];
+        let mut renderer = Renderer::new(20, 20);
+        let mut zbuffer = renderer.new_zbuffer();
+        assert!(draw(&mut renderer, sliver, &mut zbuffer).is_ok());
+        assert_ne!(renderer.image.get(17, 5).unwrap().raw[..3], [0, 0, 0]);
+
+        // render_model fills the same interior with either rasterizer; the scanline fill also
+        // takes in pixels the edges pass through, centers or not
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let render = |rasterizer| {
+            let mut renderer = Renderer::new(20, 20);
+            renderer.set_rasterizer(rasterizer);
+            renderer.render_model(&model, &texture, None).unwrap();
+            renderer.image
+        };
+        let (scanline, bary) = (
+            render(Rasterizer::Scanline),
+            render(Rasterizer::Barycentric),
+        );
+        let drawn = |image: &TGAImage, x, y| image.get(x, y).unwrap().raw[..3] != [0, 0, 0];
+        for (x, y) in [(0, 0), (10, 5), (3, 15)] {
+            assert!(drawn(&bary, x, y), "({}, {})", x, y);
+        }
+        for y in 0..20 {
+            for x in 0..20 {
+                assert!(
+                    !drawn(&bary, x, y) || drawn(&scanline, x, y),
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_intensity_saturates() {
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(200, 100, 0, 255));
+        let uv = Vec2f::new(0.0, 0.0);
+
+        let mut renderer = Renderer::new(10, 10);
+        let mut zbuffer = renderer.new_zbuffer();
+        renderer
+            .draw_triangle(
+                Vec3f::new(0.0, 0.0, 0.0),
+                Vec3f::new(9.0, 0.0, 0.0),
+                Vec3f::new(0.0, 9.0, 0.0),
+                uv,
+                uv,
+                uv,
+                &texture,
+                &[],
+                [1.5; 3],
+                0,
+                &mut zbuffer,
+            )
+            .unwrap();
+
+        let [b, g, r, _] = renderer.image.get(2, 2).unwrap().raw;
+        assert_eq!((r, g, b), (255, 150, 0));
+    }
+
+    #[test]
+    fn test_alpha_cutoff() {
+        // Opaque red on the left half, fully transparent on the right
+        let mut texture = TGAImage::new(2, 1, Format::RGBA);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 0));
+        let background = TGAColor::rgba(0, 255, 0, 255);
+
+        for cutoff in [None, Some(0.5)] {
+            let mut renderer = Renderer::new(10, 10);
+            renderer.set_alpha_cutoff(cutoff);
+            for x in 0..10 {
+                for y in 0..10 {
+                    renderer.image.set(x, y, &background);
+                }
+            }
+            let mut zbuffer = renderer.new_zbuffer();
+            let (uv_left, uv_right) = (Vec2f::new(0.0, 0.0), Vec2f::new(1.0, 0.0));
+            renderer
+                .draw_triangle(
+                    Vec3f::new(0.0, 0.0, 0.0),
+                    Vec3f::new(9.0, 0.0, 0.0),
+                    Vec3f::new(0.0, 9.0, 0.0),
+                    uv_left,
+                    uv_right,
+                    uv_left,
+                    &texture,
+                    &[],
+                    [1.0; 3],
+                    0,
+                    &mut zbuffer,
+                )
+                .unwrap();
+
+            assert_eq!(renderer.image.get(1, 1).unwrap().raw[..3], [0, 0, 255]);
+            assert_ne!(zbuffer[1 + 10], 1000.0);
+            let right = renderer.image.get(7, 1).unwrap();
+            if cutoff.is_some() {
+                assert_eq!(right.raw[..3], background.raw[..3]);
+                assert_eq!(zbuffer[7 + 10], 1000.0);
+            } else {
+                assert_eq!(right.raw[..3], [255, 0, 0]);
+                assert_ne!(zbuffer[7 + 10], 1000.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_scene() {
+        let near = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let far = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut red = TGAImage::new(1, 1, Format::RGB);
+        red.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        let mut blue = TGAImage::new(1, 1, Format::RGB);
+        blue.set(0, 0, &TGAColor::rgba(0, 0, 255, 255));
+        let forward = Matrix::from_array([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        // The near model must win whichever order the scene lists them in.
+        for near_first in [true, false] {
+            let near_item = (&near, &red, forward.clone());
+            let far_item = (&far, &blue, Matrix::identity(4));
+            let items = if near_first {
+                [near_item, far_item]
+            } else {
+                [far_item, near_item]
+            };
+
+            let mut renderer = Renderer::new(20, 20);
+            renderer.render_scene(&items).unwrap();
+            let [b, g, r, _] = renderer.image.get(5, 5).unwrap().raw;
+            assert_eq!((r, g, b), (255, 0, 0));
+        }
+    }
+
+    #[test]
+    fn test_render_model_set_viewport() {
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_viewport(Renderer::viewport_from_rect(0, 0, 10, 20));
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        // The quad fills the left half of the image and leaves the right half untouched.
+        assert_ne!(renderer.image.get(5, 10).unwrap().raw[..3], [0, 0, 0]);
+        assert_eq!(renderer.image.get(15, 10).unwrap().raw[..3], [0, 0, 0]);
+        let zbuffer = renderer.depth_buffer.as_ref().unwrap();
+        assert_ne!(zbuffer[5 + 10 * 20], 1000.0);
+        assert_eq!(zbuffer[15 + 10 * 20], 1000.0);
+    }
+
+    #[test]
+    fn test_render_model_viewport_out_of_bounds() {
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_viewport(Renderer::viewport_from_rect(0, 0, 40, 20));
+        let err = RenderError::ViewportOutOfBounds {
+            x0: 0.0,
+            y0: 0.0,
+            x1: 40.0,
+            y1: 20.0,
+            width: 20,
+            height: 20,
+        };
+        assert_eq!(
+            renderer.render_model(&model, &texture, None),
+            Err(err.clone())
+        );
+        assert_eq!(renderer.render_model_face_ids(&model), Err(err));
+        // Nothing was drawn before the viewport was rejected
+        assert!((0..20).all(|x| renderer.image.get(x, 10).unwrap().raw[..3] == [0, 0, 0]));
+    }
+
+    #[test]
+    fn test_world_to_screen() {
+        let (width, height) = (40, 20);
+        let mut renderer = Renderer::new(width, height);
+        renderer.set_view(Matrix::identity(4));
+        renderer.set_projection(Matrix::identity(4));
+        renderer.set_viewport(Renderer::viewport_from_rect(0, 0, width, height));
+
+        for p in [
+            Vec3f::new(0.0, 0.0, 0.0),
+            Vec3f::new(-1.0, -1.0, 0.0),
+            Vec3f::new(0.5, -0.25, 0.3),
+        ] {
+            let expected = Vec2i::new(
+                ((p.x + 1.0) * width as f32 / 2.0) as i32,
+                ((p.y + 1.0) * height as f32 / 2.0) as i32,
+            );
+            let actual = renderer.world_to_screen(p);
+            assert_eq!((actual.x, actual.y), (expected.x, expected.y));
+        }
+    }
+
+    #[test]
+    fn test_uv_edge_texel() {
+        let model = Model::new("tests/models/uv_edge.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 2, Format::RGB);
+        texture.set(1, 1, &TGAColor::rgba(255, 0, 0, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        // The top-right corner of the triangle samples uv (1, 1).
+        let [_, _, r, _] = renderer.image.get(19, 19).unwrap().raw;
+        assert_eq!(r, 255);
+    }
+
+    #[test]
+    fn test_render_model_uv() {
+        // Screen x spans u from 0 to 1 across the 20 pixel wide triangle.
+        let model = Model::new("tests/models/uv_edge.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_uv(&model).unwrap();
+
+        let [_, g, r, _] = renderer.image.get(19, 0).unwrap().raw;
+        let u = 19.5 / 20.0;
+        assert!((r as f32 - u * 255.0).abs() <= 1.0, "r = {}", r);
+        assert!(g < 16, "g = {}", g);
+
+        // Both triangles of a quad whose corners all sit at uv (0.5, 0.5)
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_uv(&model).unwrap();
+        for (x, y) in [(15, 3), (3, 15)] {
+            assert_eq!(renderer.image.get(x, y).unwrap().raw[..3], [0, 127, 127]);
+        }
+    }
+
+    #[test]
+    fn test_render_model_uv_without_vt() {
+        // A quad with no vt lines is drawn black rather than aborting the debug view. Sample
+        // both triangles it is split into.
+        let model = Model::new("tests/models/untextured.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        let white = TGAColor::rgba(255, 255, 255, 255);
+        renderer.image.set(15, 3, &white);
+        renderer.image.set(3, 15, &white);
+        renderer.render_model_uv(&model).unwrap();
+
+        let zbuffer = renderer.depth_buffer.as_ref().unwrap();
+        for (x, y) in [(15, 3), (3, 15)] {
+            assert_eq!(renderer.image.get(x, y).unwrap().raw[..3], [0, 0, 0]);
+            assert_ne!(zbuffer[(x + y * 20) as usize], 1000.0);
+        }
+    }
+
+    #[test]
+    fn test_render_model_normals() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_normals(&model).unwrap();
+
+        // The face normal (0, 0, 1) maps to (128, 128, 255).
+        let [b, g, r, _] = renderer.image.get(2, 2).unwrap().raw;
+        assert_eq!(b, 255);
+        assert!((r as i32 - 127).abs() <= 1 && (g as i32 - 127).abs() <= 1);
+
+        // Both triangles of a quad are drawn
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_normals(&model).unwrap();
+        for (x, y) in [(15, 3), (3, 15)] {
+            assert_eq!(renderer.image.get(x, y).unwrap().raw[0], 255);
+        }
+    }
+
+    #[test]
+    fn test_render_model_normals_model_matrix() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        // Turn the triangle about y so its normal becomes (0.8, 0, 0.6)
+        renderer.mul_matrix(Matrix::from_array([
+            [0.6, 0.0, 0.8, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-0.8, 0.0, 0.6, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+        renderer.render_model_normals(&model).unwrap();
+
+        // The turned triangle spans x in 4..16 only
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [0, 0, 0]);
+        let [b, g, r, _] = renderer.image.get(6, 4).unwrap().raw;
+        assert!((r as i32 - 229).abs() <= 1);
+        assert!((g as i32 - 127).abs() <= 1);
+        assert!((b as i32 - 204).abs() <= 1);
+    }
+
+    #[test]
+    fn test_render_model_normals_without_vn() {
+        // A quad with no vn lines falls back to its face normal (0, 0, 1)
+        let model = Model::new("tests/models/untextured.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_normals(&model).unwrap();
+
+        for (x, y) in [(15, 3), (3, 15)] {
+            let [b, g, r, _] = renderer.image.get(x, y).unwrap().raw;
+            assert_eq!(b, 255);
+            assert!((r as i32 - 127).abs() <= 1 && (g as i32 - 127).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_render_error_viewport_out_of_bounds() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let camera = CameraMode::Orthographic {
+            size: 1.0,
+            near: 0.1,
+            far: 100.0,
+        };
+        let mut renderer = Renderer::new(20, 10);
+        renderer.set_viewport(Renderer::viewport_from_rect(0, 0, 40, 10));
+        let err = renderer
+            .render_model_with_camera(&model, camera)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            RenderError::ViewportOutOfBounds {
+                x0: 0.0,
+                y0: 0.0,
+                x1: 40.0,
+                y1: 10.0,
+                width: 20,
+                height: 10,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Viewport spans pixels (0, 0) to (40, 10), outside the 20x10 framebuffer"
+        );
+
+        renderer.set_viewport(Renderer::viewport_from_rect(0, 0, 20, 10));
+        assert!(renderer.render_model_with_camera(&model, camera).is_ok());
+    }
+
+    #[test]
+    fn test_render_error_division_by_zero() {
+        // All three vertices fall within one scanline without being exactly level.
+        let mut renderer = Renderer::new(20, 20);
+        let mut zbuffer = renderer.new_zbuffer();
+        let result = renderer.draw_triangle_flat(
+            Vec3f::new(0.0, 5.0, 0.0),
+            Vec3f::new(10.0, 5.0, 0.0),
+            Vec3f::new(5.0, 5.5, 0.0),
+            &TGAColor::rgba(255, 255, 255, 255),
+            0,
+            &mut zbuffer,
+        );
+        assert_eq!(result, Err(RenderError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_render_error_texture_out_of_bounds() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let texture = TGAImage::new(0, 0, Format::RGB);
+
+        let mut renderer = Renderer::new(20, 20);
+        match renderer.render_model(&model, &texture, None) {
+            Err(RenderError::TextureOutOfBounds { x, y }) => {
+                assert!((0..20).contains(&x) && (0..20).contains(&y));
+            }
+            other => panic!("expected TextureOutOfBounds, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_uv_transform() {
+        // The left texel is green and the right one red; mapping u into [0.5, 1] keeps every
+        // fragment, including the uv (0, 0) corner, on the red half.
+        let model = Model::new("tests/models/uv_edge.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(0, 255, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(255, 0, 0, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_uv_transform(Vec2f::new(0.5, 1.0), Vec2f::new(0.5, 0.0));
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        let [b, g, r, _] = renderer.image.get(1, 0).unwrap().raw;
+        assert_eq!([b, g, r], [0, 0, 255]);
+        for y in 0..20 {
+            for x in 0..20 {
+                let [_, g, _, _] = renderer.image.get(x, y).unwrap().raw;
+                assert_eq!(g, 0, "green texel sampled at ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flip_texture_v() {
+        // Row 0 of the texture is red and row 1 green; the pixel sampled is next to the uv
+        // (1, 0) corner.
+        let model = Model::new("tests/models/uv_edge.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 2, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(0, 1, &TGAColor::rgba(0, 255, 0, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(18, 1).unwrap().raw[..3], [0, 0, 255]);
+
+        renderer.set_flip_texture_v(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(18, 1).unwrap().raw[..3], [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_shadow_pcf() {
+        // The light slants toward +x, so the floating quad shadows the floor to its right.
+        let model = Model::new("tests/models/shadow.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let render = |kernel: u32| {
+            let mut renderer = Renderer::builder()
+                .width(40)
+                .height(40)
+                .light(Vec3f::new(1.0, 0.0, -1.0))
+                .build();
+            renderer.set_shadows(true);
+            renderer.set_shadow_pcf(kernel);
+            renderer.render_model(&model, &texture, None).unwrap();
+            (0..40)
+                .map(|x| renderer.image.get(x, 20).unwrap().raw[0])
+                .collect::<Vec<u8>>()
+        };
+
+        let hard = render(1);
+        let lit = hard[39];
+        assert!(lit > 150);
+        assert_eq!(hard[22], 0);
+        assert!(hard.iter().all(|&c| c == 0 || c == lit), "{:?}", hard);
+
+        let soft = render(5);
+        assert_eq!(soft[22], 0);
+        assert!(soft.iter().any(|&c| c > 0 && c < lit), "{:?}", soft);
+    }
+
+    #[test]
+    fn test_uv_lod() {
+        let texture = TGAImage::new(256, 256, Format::RGB);
+        let triangle =
+            |size: f32| [(0.0, 0.0), (size, 0.0), (0.0, size)].map(|(x, y)| Vec3f::new(x, y, 0.0));
+        let uvs = |size: f32| [(0.0, 0.0), (size, 0.0), (0.0, size)].map(|(u, v)| Vec2f::new(u, v));
+
+        // The whole texture squeezed into 4 pixels crosses 64 texels per pixel.
+        let lod = Renderer::uv_lod(triangle(4.0), uvs(1.0), &texture);
+        assert!((lod - 6.0).abs() < 1e-4, "{}", lod);
+        // A few texels stretched over 100 pixels are magnified and use the full resolution.
+        assert_eq!(Renderer::uv_lod(triangle(100.0), uvs(0.01), &texture), 0.0);
+        // One texel per pixel is the boundary.
+        assert_eq!(Renderer::uv_lod(triangle(256.0), uvs(1.0), &texture), 0.0);
+    }
+
+    #[test]
+    fn test_reverse_z() {
+        // Near face 0 overlaps far face 1, which is listed second.
+        let model = Model::new("tests/models/overlap.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 255));
+        let render = |reverse_z: bool, clear_depth: Option<f32>| {
+            let mut renderer = Renderer::new(20, 20);
+            renderer.set_reverse_z(reverse_z);
+            renderer.set_clear_depth(clear_depth);
+            renderer.render_model(&model, &texture, None).unwrap();
+            renderer
+        };
+
+        let standard = render(false, None);
+        let reverse = render(true, None);
+        assert_eq!(standard.image.get(8, 8).unwrap().raw[..3], [0, 0, 255]);
+        for y in 0..20 {
+            for x in 0..20 {
+                assert_eq!(
+                    reverse.image.get(x, y).unwrap().raw,
+                    standard.image.get(x, y).unwrap().raw
+                );
+            }
+        }
+
+        // The default range puts the near face closer to 0 and clears to the far end; reverse-Z
+        // flips both.
+        let depth_at = |renderer: &Renderer, x: usize, y: usize| {
+            renderer.depth_buffer.as_ref().unwrap()[x + y * 20]
+        };
+        let (near, far) = (depth_at(&standard, 8, 8), depth_at(&standard, 15, 8));
+        assert!((0.0..far).contains(&near) && far < 1000.0);
+        assert_eq!(depth_at(&standard, 19, 19), 1000.0);
+        assert_eq!(depth_at(&reverse, 8, 8), 1000.0 - near);
+        assert_eq!(depth_at(&reverse, 15, 8), 1000.0 - far);
+        assert_eq!(depth_at(&reverse, 19, 19), 0.0);
+
+        // Clearing to a depth between the faces discards the far one in either convention.
+        let between = (near + far) / 2.0;
+        for clipped in [
+            render(false, Some(between)),
+            render(true, Some(1000.0 - between)),
         ] {
-            let mut renderer = Renderer::new(width, height);
-            renderer.draw_line(
-                Vec2i::new(0, 0),
-                Vec2i::new(8, 5),
-                c,
-                test.optimization_level,
+            assert_eq!(clipped.image.get(8, 8).unwrap().raw[..3], [0, 0, 255]);
+            assert_eq!(clipped.image.get(15, 8).unwrap().raw[..3], [0, 0, 0]);
+        }
+        assert_ne!(standard.image.get(15, 8).unwrap().raw[..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_double_sided() {
+        // The second face of winding.obj is wound clockwise, so it faces away from the viewer.
+        let model = Model::new("tests/models/winding.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(18, 18).unwrap().raw[..3], [0, 0, 0]);
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_double_sided(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(
+            renderer.image.get(18, 18).unwrap().raw[..3],
+            [255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn test_cull_mode() {
+        // Face 0 of winding.obj faces the viewer and face 1, at the far corner, faces away.
+        let model = Model::new("tests/models/winding.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(200, 200, 200, 255));
+        let render = |mode: Option<CullMode>| {
+            let mut renderer = Renderer::builder()
+                .width(20)
+                .height(20)
+                .ambient(0.25)
+                .build();
+            if let Some(mode) = mode {
+                renderer.set_cull_mode(mode);
+            }
+            let stats = renderer.render_model(&model, &texture, None).unwrap();
+            let level = |x, y| renderer.image.get(x, y).unwrap().raw[0];
+            (stats.faces_culled, [level(12, 12), level(18, 18)])
+        };
+
+        assert_eq!(render(None), (1, [250, 0]));
+        assert_eq!(render(Some(CullMode::Back)), (1, [250, 0]));
+        assert_eq!(render(Some(CullMode::Front)), (1, [0, 50]));
+        // The back face faces away from the light too, so only the ambient term lights it
+        assert_eq!(render(Some(CullMode::None)), (0, [250, 50]));
+
+        let renderer = Renderer::builder().cull_backfaces(false).build();
+        assert_eq!(renderer.cull_mode, CullMode::None);
+    }
+
+    #[test]
+    fn test_texture_srgb() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(128, 128, 128, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_texture_srgb(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        // The triangle faces the light head on, so decoding and re-encoding is a round trip.
+        let [b, g, r, _] = renderer.image.get(2, 2).unwrap().raw;
+        for c in [b, g, r] {
+            assert!((c as i32 - 128).abs() <= 1, "channel = {}", c);
+        }
+    }
+
+    #[test]
+    fn test_hdr_accumulation() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        // Each light hits the triangle at 60 degrees, contributing half intensity
+        let mut renderer = Renderer::builder()
+            .width(20)
+            .height(20)
+            .light(Vec3f::new(0.866, 0.0, -0.5))
+            .light(Vec3f::new(-0.866, 0.0, -0.5))
+            .build();
+        renderer.set_hdr(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+        for c in renderer.hdr_image().unwrap().get(2, 2).unwrap() {
+            assert!((c - 1.0).abs() < 1e-3, "channel = {}", c);
+        }
+
+        // Doubling the lights goes past what the 8-bit framebuffer can hold
+        let mut renderer = Renderer::builder()
+            .width(20)
+            .height(20)
+            .light(Vec3f::new(0.866, 0.0, -0.5))
+            .light(Vec3f::new(-0.866, 0.0, -0.5))
+            .light(Vec3f::new(0.0, 0.866, -0.5))
+            .light(Vec3f::new(0.0, -0.866, -0.5))
+            .build();
+        renderer.set_hdr(true);
+        renderer.render_model(&model, &texture, None).unwrap();
+        for c in renderer.hdr_image().unwrap().get(2, 2).unwrap() {
+            assert!((c - 2.0).abs() < 1e-3, "channel = {}", c);
+        }
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [255, 255, 255]);
+        assert_eq!(
+            renderer
+                .hdr_image()
+                .unwrap()
+                .tone_map_reinhard()
+                .get(2, 2)
+                .unwrap()
+                .raw[..3],
+            [170, 170, 170]
+        );
+    }
+
+    #[test]
+    fn test_missing_texel_color() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(0, 255, 0, 255));
+        let magenta = TGAColor::rgba(255, 0, 255, 255);
+
+        // Shift every uv two texture widths to the right
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_uv_transform(Vec2f::new(1.0, 1.0), Vec2f::new(2.0, 0.0));
+        renderer.set_missing_texel_color(Some(magenta));
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], magenta.raw[..3]);
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_uv_transform(Vec2f::new(1.0, 1.0), Vec2f::new(2.0, 0.0));
+        renderer.set_missing_texel_color(None);
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [0, 255, 0]);
+
+        // Nothing can be sampled from an empty texture, so the fill color is used throughout
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_missing_texel_color(Some(magenta));
+        let empty = TGAImage::new(0, 0, Format::RGB);
+        renderer.render_model(&model, &empty, None).unwrap();
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], magenta.raw[..3]);
+    }
+
+    #[test]
+    fn test_wrap_mode() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(2, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        texture.set(1, 0, &TGAColor::rgba(0, 0, 255, 255));
+        let (red, blue) = ([0, 0, 255], [255, 0, 0]);
+        // The model's single uv is (0.5, 0.5), shifted here to u = 0.5 + offset
+        let render = |mode: Option<WrapMode>, offset: f32| {
+            let mut renderer = Renderer::new(20, 20);
+            if let Some(mode) = mode {
+                renderer.set_wrap_mode(mode);
+            }
+            renderer.set_uv_transform(Vec2f::new(1.0, 1.0), Vec2f::new(offset, 0.0));
+            renderer.render_model(&model, &texture, None).unwrap();
+            renderer.image.get(2, 2).unwrap().raw[..3].to_vec()
+        };
+
+        for (offset, clamped, repeated) in
+            [(0.75, blue, red), (-0.75, red, blue), (1.875, blue, red)]
+        {
+            assert_eq!(render(None, offset), clamped, "offset {}", offset);
+            assert_eq!(
+                render(Some(WrapMode::Clamp), offset),
+                clamped,
+                "offset {}",
+                offset
+            );
+            assert_eq!(
+                render(Some(WrapMode::Repeat), offset),
+                repeated,
+                "offset {}",
+                offset
             );
+        }
+    }
 
-            let mut testimage = TGAImage::new(width, height, Format::RGB);
-            testimage.read_tga_file(test.filename).unwrap();
-            testimage.flip_vertically();
+    #[test]
+    fn test_skip_sub_pixel_triangles() {
+        let mut renderer = Renderer::new(20, 20);
+        let mut zbuffer = renderer.new_zbuffer();
+        let white = TGAColor::rgba(255, 255, 255, 255);
 
-            assert_eq!(renderer.image.data, testimage.data);
+        // Collinear vertices spanning several scanlines
+        renderer
+            .draw_triangle_flat(
+                Vec3f::new(0.0, 0.0, 0.0),
+                Vec3f::new(5.0, 5.0, 0.0),
+                Vec3f::new(10.0, 10.0, 0.0),
+                &white,
+                0,
+                &mut zbuffer,
+            )
+            .unwrap();
+        // A sliver far thinner than a pixel
+        renderer
+            .draw_triangle_flat(
+                Vec3f::new(2.0, 2.0, 0.0),
+                Vec3f::new(2.0, 12.0, 0.0),
+                Vec3f::new(2.05, 7.0, 0.0),
+                &white,
+                0,
+                &mut zbuffer,
+            )
+            .unwrap();
+        assert_eq!(count_set_pixels(&renderer.image), 0);
+    }
+
+    #[test]
+    fn test_polygon_offset() {
+        let white = TGAColor::rgba(255, 255, 255, 255);
+        let red = TGAColor::rgba(255, 0, 0, 255);
+        // A sloped surface and a smaller decal lying in the same plane
+        let plane = |x: f32, y: f32| Vec3f::new(x, y, 0.5 * x + 0.25 * y);
+        let surface = [plane(0.0, 0.0), plane(19.0, 0.0), plane(0.0, 19.0)];
+        let decal = [plane(2.0, 2.0), plane(12.0, 2.0), plane(2.0, 12.0)];
+
+        for decal_first in [false, true] {
+            let mut renderer = Renderer::new(20, 20);
+            let mut zbuffer = renderer.new_zbuffer();
+            let mut draw = |renderer: &mut Renderer, [t0, t1, t2]: [Vec3f; 3], color, offset| {
+                renderer.set_polygon_offset(offset, offset);
+                renderer
+                    .draw_triangle_flat(t0, t1, t2, color, 0, &mut zbuffer)
+                    .unwrap();
+            };
+            if decal_first {
+                draw(&mut renderer, decal, &red, -1.0);
+                draw(&mut renderer, surface, &white, 0.0);
+            } else {
+                draw(&mut renderer, surface, &white, 0.0);
+                draw(&mut renderer, decal, &red, -1.0);
+            }
+            for (x, y) in [(3, 3), (6, 4), (4, 7)] {
+                assert_eq!(
+                    renderer.image.get(x, y).unwrap().raw[..3],
+                    red.raw[..3],
+                    "({}, {}), decal first: {}",
+                    x,
+                    y,
+                    decal_first
+                );
+            }
+            assert_eq!(renderer.image.get(15, 2).unwrap().raw[..3], white.raw[..3]);
+        }
+    }
+
+    #[test]
+    fn test_render_quad() {
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        // One pixel on each side of the diagonal
+        assert_eq!(renderer.image.get(17, 2).unwrap().raw[..3], [255, 255, 255]);
+        assert_eq!(renderer.image.get(2, 17).unwrap().raw[..3], [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_model_tiled() {
+        let model = Model::new("tests/models/cube.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        // Turned about y and x so several faces overlap in depth
+        let (c, s) = (0.5f32.cos() * 0.6, 0.5f32.sin() * 0.6);
+        let turn = Matrix::from_array([
+            [c, 0.0, s, 0.0],
+            [0.0, 0.6, 0.0, 0.0],
+            [-s, 0.0, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]) * Matrix::from_array([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, c / 0.6, -s / 0.6, 0.0],
+            [0.0, s / 0.6, c / 0.6, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let mut whole = Renderer::new(30, 20);
+        whole.mul_matrix(turn.clone());
+        let expected = whole.render_model(&model, &texture, None).unwrap();
+
+        let mut tiled = Renderer::new(30, 20);
+        tiled.mul_matrix(turn);
+        let mut covered = vec![0; 30 * 20];
+        let stats = tiled
+            .render_model_tiled(&model, &texture, 8, |tile, _| {
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(x + y * 30) as usize] += 1;
+                    }
+                }
+            })
+            .unwrap();
+
+        assert!(covered.iter().all(|&n| n == 1));
+        assert!(expected.pixels_shaded > 0 && expected.depth_tests_failed > 0);
+        assert_eq!(tiled.image.data, whole.image.data);
+        assert_eq!(stats.faces_total, expected.faces_total);
+        assert_eq!(stats.pixels_shaded, expected.pixels_shaded);
+    }
+
+    #[test]
+    fn test_render_model_flat() {
+        // Four corners and a quad face, with neither uvs nor normals
+        let model = Model::new("tests/models/untextured.obj").expect("Failed to load model.");
+        let base = TGAColor::rgba(200, 100, 50, 255);
+
+        let mut renderer = Renderer::new(20, 20);
+        let stats = renderer.render_model_flat(&model, base).unwrap();
+        assert_eq!(stats.triangles_drawn, 2);
+        assert_eq!(renderer.image.get(3, 15).unwrap().raw[..3], base.raw[..3]);
+        assert_eq!(renderer.image.get(15, 3).unwrap().raw[..3], base.raw[..3]);
+
+        // The base color is lit like a texture, and Gouraud falls back to the face normal
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_shading(ShadingMode::Gouraud);
+        renderer.set_light_dir(Vec3f::new(3f32.sqrt(), 0.0, -1.0));
+        renderer.render_model_flat(&model, base).unwrap();
+        assert_eq!(renderer.image.get(10, 10).unwrap().raw[..3], [25, 50, 100]);
+    }
+
+    #[test]
+    fn test_set_texture() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(0, 0, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_texture(Some(texture));
+        renderer.render_model_with_stored_texture(&model).unwrap();
+        let first = renderer.image.data.clone();
+        renderer.render_model_with_stored_texture(&model).unwrap();
+        assert_eq!(renderer.image.data, first);
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [255, 0, 0]);
+
+        renderer.set_texture(None);
+        renderer.render_model_with_stored_texture(&model).unwrap();
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_shared_edge_fill_rule() {
+        // Two triangles split a square along its diagonal, and the second one is nearer, so
+        // every pixel it shades is counted even where the first one already drew.
+        let mut renderer = Renderer::new(20, 20);
+        let mut zbuffer = renderer.new_zbuffer();
+        let hits = std::cell::RefCell::new(vec![vec![0; 20]; 20]);
+        let triangles = [
+            [(0.0, 0.0), (16.0, 0.0), (16.0, 16.0)],
+            [(0.0, 0.0), (16.0, 16.0), (0.0, 16.0)],
+        ];
+        for (z, triangle) in triangles.into_iter().enumerate() {
+            let [t0, t1, t2] = triangle.map(|(x, y)| Vec3f::new(x, y, z as f32));
+            renderer.draw_triangle_shaded(
+                [t0, t1, t2],
+                z,
+                &mut zbuffer,
+                DepthPass::Standard,
+                |w| {
+                    let p = t0 * w[0] + t1 * w[1] + t2 * w[2];
+                    hits.borrow_mut()[p.x as usize][p.y as usize] += 1;
+                    TGAColor::rgba(255, 255, 255, 255)
+                },
+            );
+        }
+
+        for (x, column) in hits.into_inner().iter().enumerate() {
+            for (y, &count) in column.iter().enumerate() {
+                let expected = if x < 16 && y < 16 { 1 } else { 0 };
+                assert_eq!(count, expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_edge_fill_rule_barycentric() {
+        // The quad's two triangles meet along a diagonal through pixel centers, at equal depth,
+        // so a pixel claimed by both would fail the depth test the second time.
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_rasterizer(Rasterizer::Barycentric);
+        let stats = renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(stats.triangles_drawn, 2);
+        assert_eq!(stats.pixels_shaded, 20 * 20);
+        assert_eq!(stats.depth_tests_failed, 0);
+    }
+
+    #[test]
+    fn test_render_model_auto() {
+        let model = Model::new("tests/models/textured.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_auto(&model).unwrap();
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [255, 0, 0]);
+
+        let model = Model::new("tests/models/missing_texture.obj").expect("Failed to load model.");
+        assert_eq!(
+            renderer.render_model_auto(&model),
+            Err(RenderError::MissingTexture(
+                "tests/models/does_not_exist.tga".into()
+            ))
+        );
+
+        let model = Model::new("tests/models/missing_mtl.obj").expect("Failed to load model.");
+        assert_eq!(
+            renderer.render_model_auto(&model),
+            Err(RenderError::MissingMaterialLibrary(
+                "tests/models/does_not_exist.mtl".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_render_material_textures() {
+        // The left face uses the blue-textured material and the right one the red-textured
+        // material with its diffuse color at half strength.
+        let model = Model::new("tests/models/two_materials.obj").expect("Failed to load model.");
+        let mut white = TGAImage::new(1, 1, Format::RGB);
+        white.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model(&model, &white, None).unwrap();
+
+        assert_eq!(renderer.image.get(2, 2).unwrap().raw[..3], [255, 0, 0]);
+        assert_eq!(renderer.image.get(18, 2).unwrap().raw[..3], [0, 0, 127]);
+    }
+
+    #[test]
+    fn test_set_light_dir() {
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(200, 200, 200, 255));
+        let render = |dir: Option<Vec3f>| {
+            let mut renderer = Renderer::new(20, 20);
+            if let Some(dir) = dir {
+                renderer.set_light_dir(dir);
+            }
+            renderer.render_model(&model, &texture, None).unwrap();
+            renderer.image.get(5, 5).unwrap().raw[0]
+        };
+
+        assert_eq!(render(None), 200);
+        // Lengths do not matter
+        assert_eq!(render(Some(Vec3f::new(0.0, 0.0, -5.0))), 200);
+        // 60 degrees off the normal halves the intensity
+        let tilted = render(Some(Vec3f::new(3f32.sqrt(), 0.0, -1.0)));
+        assert!((tilted as i32 - 100).abs() <= 1, "tilted = {}", tilted);
+        // Grazing and back lights leave the face unlit
+        assert_eq!(render(Some(Vec3f::new(1.0, 0.0, 0.0))), 0);
+        assert_eq!(render(Some(Vec3f::new(0.0, 0.0, 1.0))), 0);
+    }
+
+    #[test]
+    fn test_gouraud_shading() {
+        // The bottom-right vertex normal is tilted 45 degrees from +z towards +x.
+        let model = Model::new("tests/models/gouraud.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(100, 100, 100, 255));
+        let render = |shading| {
+            let mut renderer = Renderer::new(20, 20);
+            renderer.set_shading(shading);
+            renderer.render_model(&model, &texture, None).unwrap();
+            let level = |x, y| renderer.image.get(x, y).unwrap().raw[0] as i32;
+            [level(1, 0), level(9, 0), level(17, 0), level(1, 17)]
+        };
+
+        // The geometric normal faces the default light head on everywhere
+        assert_eq!(render(ShadingMode::Flat), [100; 4]);
+        // Towards the tilted normal the intensity falls off to cos 45 = 0.71
+        let [left, middle, right, top] = render(ShadingMode::Gouraud);
+        assert!((left - 100).abs() <= 2 && (top - 100).abs() <= 2);
+        assert!(
+            left > middle && middle > right,
+            "{} {} {}",
+            left,
+            middle,
+            right
+        );
+        assert!((right - 73).abs() <= 3, "right = {}", right);
+    }
+
+    #[test]
+    fn test_renderer_builder() {
+        // The bottom-right vertex normal is tilted 45 degrees from +z towards +x.
+        let model = Model::new("tests/models/gouraud.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(100, 100, 100, 255));
+
+        let mut renderer = Renderer::builder()
+            .width(20)
+            .height(20)
+            .shading(ShadingMode::Gouraud)
+            .light(Vec3f::new(0.0, 0.0, -1.0))
+            .light(Vec3f::new(-1.0, 0.0, 0.0))
+            .gamma(2.2)
+            .build();
+        assert_eq!((renderer.image.width, renderer.image.height), (20, 20));
+        assert_eq!(renderer.gamma, 2.2);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        // +z only faces the first light. The tilted normal faces both at 45 degrees, adding up
+        // to about 1.41, where flat shading or a single light would give 1.0 or 0.71.
+        let [left, ..] = renderer.image.get(1, 0).unwrap().raw;
+        let [right, ..] = renderer.image.get(18, 0).unwrap().raw;
+        assert!((left as i32 - 100).abs() <= 5, "left = {}", left);
+        assert!((right as i32 - 141).abs() <= 5, "right = {}", right);
+    }
+
+    #[test]
+    fn test_draw_line_clipped() {
+        let white = TGAColor::rgba(255, 255, 255, 255);
+        let set_pixels = |image: &TGAImage, offset: i32| {
+            let mut pixels = Vec::new();
+            for y in 0..10 {
+                for x in 0..10 {
+                    if image.get(x + offset, y + offset).unwrap().raw[0] != 0 {
+                        pixels.push((x, y));
+                    }
+                }
+            }
+            pixels
+        };
+
+        // The same line drawn fully inside a larger canvas gives the expected in-bounds pixels.
+        let mut reference = Renderer::new(30, 30);
+        reference.draw_line(
+            Vec2i::new(3, 12),
+            Vec2i::new(27, 21),
+            &white,
+            OptimizationLevel::default(),
+        );
+        let expected = set_pixels(&reference.image, 10);
+        assert!(!expected.is_empty());
+
+        for level in [OptimizationLevel::Level2, OptimizationLevel::Level3] {
+            let mut renderer = Renderer::new(10, 10);
+            renderer.draw_line(Vec2i::new(-7, 2), Vec2i::new(17, 11), &white, level);
+            assert_eq!(set_pixels(&renderer.image, 0), expected, "{:?}", level);
+        }
+
+        // Level1 rounds differently, so compare it against its own unclipped line
+        let mut reference = Renderer::new(30, 30);
+        reference.draw_line(
+            Vec2i::new(3, 12),
+            Vec2i::new(27, 21),
+            &white,
+            OptimizationLevel::Level1,
+        );
+        let mut renderer = Renderer::new(10, 10);
+        renderer.draw_line(
+            Vec2i::new(-7, 2),
+            Vec2i::new(17, 11),
+            &white,
+            OptimizationLevel::Level1,
+        );
+        assert_eq!(
+            set_pixels(&renderer.image, 0),
+            set_pixels(&reference.image, 10)
+        );
+    }
+
+    #[test]
+    fn test_draw_line_cohen_sutherland() {
+        let white = TGAColor::rgba(255, 255, 255, 255);
+
+        // Reference drawn entirely inside a canvas large enough to need no clipping
+        let mut reference = Renderer::new(1001, 1001);
+        reference.draw_line(
+            Vec2i::new(0, 0),
+            Vec2i::new(1000, 1000),
+            &white,
+            OptimizationLevel::Level2,
+        );
+        let mut renderer = Renderer::new(800, 800);
+        renderer.draw_line(
+            Vec2i::new(-100, -100),
+            Vec2i::new(900, 900),
+            &white,
+            OptimizationLevel::Level2,
+        );
+        for y in 0..800 {
+            for x in 0..800 {
+                assert_eq!(
+                    renderer.image.get(x, y).unwrap().raw,
+                    reference.image.get(x + 100, y + 100).unwrap().raw,
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+
+        // Only the on-canvas columns are iterated, give or take the clipping margin
+        let range = Renderer::visible_major_range(-100, -100, 900, 900, 800, 800).unwrap();
+        assert!(*range.start() >= 0 && *range.end() <= 799, "{:?}", range);
+        assert_eq!(
+            Renderer::visible_major_range(-100, 900, 900, 1900, 800, 800),
+            None
+        );
+
+        // Shallow lines entering through the top and leaving through the side match too
+        let mut reference = Renderer::new(2000, 2000);
+        reference.draw_line(
+            Vec2i::new(0, 1100),
+            Vec2i::new(1999, 300),
+            &white,
+            OptimizationLevel::Level2,
+        );
+        let mut renderer = Renderer::new(800, 800);
+        renderer.draw_line(
+            Vec2i::new(-600, 800),
+            Vec2i::new(1399, 0),
+            &white,
+            OptimizationLevel::Level2,
+        );
+        for y in 0..800 {
+            for x in 0..800 {
+                assert_eq!(
+                    renderer.image.get(x, y).unwrap().raw,
+                    reference.image.get(x + 600, y + 300).unwrap().raw,
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        let mask = (0..20 * 20).map(|i| i % 20 >= 10).collect();
+        renderer.set_mask(mask);
+        renderer.render_model(&model, &texture, None).unwrap();
+        for y in 0..20 {
+            for x in 0..20 {
+                let lit = renderer.image.get(x, y).unwrap().raw[0] != 0;
+                assert_eq!(lit, x >= 10, "pixel ({}, {})", x, y);
+            }
+        }
+
+        renderer.clear_mask();
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(count_set_pixels(&renderer.image), 20 * 20);
+    }
+
+    #[test]
+    fn test_front_face() {
+        let ccw = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let cw = Model::new("tests/models/triangle_cw.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        for (front_face, expected) in [
+            (Winding::CounterClockwise, [true, false]),
+            (Winding::Clockwise, [false, true]),
+        ] {
+            for (model, expected) in [&ccw, &cw].into_iter().zip(expected) {
+                let mut renderer = Renderer::new(20, 20);
+                renderer.set_front_face(front_face);
+                renderer.render_model(model, &texture, None).unwrap();
+                let lit = renderer.image.get(2, 2).unwrap().raw[..3] == [255, 255, 255];
+                assert_eq!(lit, expected, "{:?}", front_face);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_model_barycentric_debug() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_barycentric_debug(&model).unwrap();
+
+        // Pixels next to the vertices at screen (0, 0), (20, 0) and (0, 20)
+        for ((x, y), channel) in [((0, 0), 2), ((18, 0), 1), ((0, 18), 0)] {
+            let raw = renderer.image.get(x, y).unwrap().raw;
+            for c in 0..3 {
+                if c == channel {
+                    assert!(raw[c] > 200, "({}, {}): {:?}", x, y, raw);
+                } else {
+                    assert!(raw[c] < 40, "({}, {}): {:?}", x, y, raw);
+                }
+            }
+        }
+
+        // Both triangles of a quad are drawn
+        let model = Model::new("tests/models/quad.obj").expect("Failed to load model.");
+        let mut renderer = Renderer::new(20, 20);
+        renderer.render_model_barycentric_debug(&model).unwrap();
+        for (x, y) in [(15, 3), (3, 15)] {
+            assert_ne!(renderer.image.get(x, y).unwrap().raw[..3], [0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn test_depth_prepass() {
+        // Face 0 is a small far triangle drawn before the large near face 1 that hides it
+        let model = Model::new("tests/models/occluded.obj").expect("Failed to load model.");
+        let render = |prepass: bool| {
+            let calls = std::cell::RefCell::new([0; 2]);
+            let mut renderer = Renderer::new(20, 20);
+            renderer.enable_depth_prepass(prepass);
+            renderer
+                .render_model_shaded(&model, |face, _, _| {
+                    calls.borrow_mut()[face] += 1;
+                    TGAColor::rgba(255, 255, 255, 255)
+                })
+                .unwrap();
+            (calls.into_inner(), renderer.image.get(5, 5).unwrap().raw)
+        };
+
+        let (calls, without) = render(false);
+        assert!(calls[0] > 0);
+        let (calls, with) = render(true);
+        assert_eq!(calls[0], 0);
+        assert!(calls[1] > 0);
+        assert_eq!(with, without);
+    }
+
+    #[test]
+    fn test_render_stats() {
+        // Face 0 is a small far triangle that face 1, drawn after it, covers entirely
+        let model = Model::new("tests/models/occluded.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+
+        let mut renderer = Renderer::new(20, 20);
+        let far_first = renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(far_first.faces_total, 2);
+        assert_eq!((far_first.faces_culled, far_first.faces_clipped), (0, 0));
+        assert_eq!(far_first.triangles_drawn, 2);
+        assert_eq!(far_first.depth_tests_failed, 0);
+
+        // Mirroring z makes face 0 the near one, so face 1 fails wherever they overlap
+        let mut renderer = Renderer::new(20, 20);
+        let mut mirror = Matrix::identity(4);
+        mirror[2][2] = -1.0;
+        renderer.mul_matrix(mirror);
+        let near_first = renderer.render_model(&model, &texture, None).unwrap();
+        assert!(near_first.depth_tests_failed > 0);
+        assert!(near_first.pixels_shaded < far_first.pixels_shaded);
+        assert_eq!(
+            near_first.pixels_shaded + near_first.depth_tests_failed,
+            far_first.pixels_shaded
+        );
+
+        // Seen from behind, both faces are culled
+        let mut renderer = Renderer::new(20, 20);
+        renderer.set_front_face(Winding::Clockwise);
+        let culled = renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!((culled.faces_culled, culled.triangles_drawn), (2, 0));
+        assert_eq!(culled.pixels_shaded, 0);
+
+        // Doubled in size, the large face reaches past the image
+        let mut renderer = Renderer::new(20, 20);
+        let mut scale = Matrix::identity(4);
+        for i in 0..3 {
+            scale[i][i] = 2.0;
+        }
+        renderer.mul_matrix(scale);
+        let clipped = renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(clipped.faces_clipped, 1);
+    }
+
+    #[test]
+    fn test_save_depth_pfm() {
+        let model = Model::new("tests/models/occluded.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let mut renderer = Renderer::new(20, 10);
+        renderer.render_model(&model, &texture, None).unwrap();
+
+        let path = std::env::temp_dir().join("tinyrenderer_test_save_depth_pfm.pfm");
+        renderer.save_depth_pfm(&path.to_string_lossy()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"Pf\n20 10\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 20 * 10 * 4);
+        let depth_at = |x: usize, y: usize| {
+            let i = header.len() + (x + y * 20) * 4;
+            f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap())
+        };
+        let zbuffer = renderer.depth_buffer.as_ref().unwrap();
+        for (x, y) in [(2, 2), (7, 4), (18, 8)] {
+            assert_eq!(depth_at(x, y), zbuffer[x + y * 20], "({}, {})", x, y);
         }
+        // z = 0.5 lands a quarter of the way into the default 0..=1000 range, nearest first
+        assert_eq!(depth_at(2, 2), 250.0);
+        assert_eq!(depth_at(18, 8), 1000.0);
+    }
+
+    #[test]
+    fn test_matrix_stack() {
+        let model = Model::new("tests/models/triangle.obj").expect("Failed to load model.");
+        let mut texture = TGAImage::new(1, 1, Format::RGB);
+        texture.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        let matrix_rows = |m: &Matrix| {
+            (0..4)
+                .map(|i| (0..4).map(|j| m[i][j]).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+
+        let mut renderer = Renderer::new(20, 20);
+        renderer.mul_matrix(Matrix::from_array([
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 0.5, 0.0, 0.0],
+            [0.0, 0.0, 0.5, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+        let before = matrix_rows(renderer.model_matrix());
+
+        renderer.push_matrix();
+        // Shift the half-size triangle right by half the screen
+        renderer.mul_matrix(Matrix::from_array([
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+        renderer.render_model(&model, &texture, None).unwrap();
+        assert_eq!(renderer.image.get(6, 6).unwrap().raw[..3], [0, 0, 0]);
+        assert_eq!(renderer.image.get(11, 6).unwrap().raw[..3], [255, 255, 255]);
+
+        assert!(renderer.pop_matrix());
+        assert_eq!(matrix_rows(renderer.model_matrix()), before);
+        assert!(!renderer.pop_matrix());
     }
 }
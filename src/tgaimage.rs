@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, BufWriter, Read, Write};
 
 #[repr(C, packed)]
 struct TGAHeader {
@@ -17,6 +17,133 @@ struct TGAHeader {
     imagedescriptor: u8,
 }
 
+/// Converts a color channel computed in floating point to a byte, saturating at 0 and 255
+/// instead of wrapping.
+pub fn clamp_u8(f: f32) -> u8 {
+    f.clamp(0.0, 255.0) as u8
+}
+
+/// Decodes an sRGB-encoded channel to linear light in [0, 1].
+pub fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light to an sRGB channel, saturating outside [0, 1].
+pub fn linear_to_srgb(l: f32) -> u8 {
+    let l = l.clamp(0.0, 1.0);
+    let c = if l <= 0.003_130_8 {
+        l * 12.92
+    } else {
+        1.055 * l.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+/// 5x7 glyphs for ASCII 32 to 126, one byte per column from left to right. Bit 0 of each
+/// column is the top row.
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x00, 0x7F, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
 #[derive(Clone, Copy, Debug)]
 pub struct TGAColor {
     pub raw: [u8; 4],
@@ -40,6 +167,50 @@ impl TGAColor {
         }
     }
 
+    /// Builds an opaque color from hue in degrees [0, 360) and saturation/value in [0, 1].
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> TGAColor {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let m = v - c;
+        let (r, g, b) = match h as i32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        TGAColor::rgba(
+            clamp_u8(((r + m) * 255.0).round()),
+            clamp_u8(((g + m) * 255.0).round()),
+            clamp_u8(((b + m) * 255.0).round()),
+            255,
+        )
+    }
+
+    /// Returns (hue in degrees, saturation, value) of the color's RGB channels.
+    #[allow(dead_code)]
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let [b, g, r, _] = self.raw.map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        (h, s, max)
+    }
+
     #[allow(dead_code)]
     pub fn from_slice(p: &[u8], bpp: usize) -> TGAColor {
         let mut raw = [0u8; 4];
@@ -48,7 +219,8 @@ impl TGAColor {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Format {
     #[allow(dead_code)]
     Grayscale = 1,
@@ -57,6 +229,7 @@ pub enum Format {
     RGBA = 4,
 }
 
+#[derive(Clone)]
 pub struct TGAImage {
     pub data: Vec<u8>,
     pub width: i32,
@@ -76,6 +249,37 @@ impl TGAImage {
         }
     }
 
+    /// Pixel format matching the bytes per pixel, or `None` for layouts `Format` cannot name.
+    #[allow(dead_code)]
+    pub fn format(&self) -> Option<Format> {
+        match self.bytespp {
+            1 => Some(Format::Grayscale),
+            3 => Some(Format::RGB),
+            4 => Some(Format::RGBA),
+            _ => None,
+        }
+    }
+
+    /// Like `read_tga_file`, but fails instead of switching formats when the file's decoded pixel
+    /// format differs from this image's. The image is left untouched on error.
+    #[allow(dead_code)]
+    pub fn read_tga_file_checked(&mut self, filename: &str) -> io::Result<()> {
+        let mut image = TGAImage::new(0, 0, Format::RGB);
+        image.read_tga_file(filename)?;
+        if image.bytespp != self.bytespp {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Expected {} bytes per pixel, file has {}",
+                    self.bytespp, image.bytespp
+                ),
+            ));
+        }
+
+        *self = image;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub fn read_tga_file(&mut self, filename: &str) -> io::Result<()> {
         let mut file = File::open(filename)?;
@@ -127,6 +331,11 @@ impl TGAImage {
             ));
         }
 
+        if self.bytespp == 2 {
+            // The low four descriptor bits count attribute (alpha) bits per pixel
+            self.expand_argb1555(header.imagedescriptor & 0x0f != 0);
+        }
+
         if (header.imagedescriptor & 0x20) == 0 {
             self.flip_vertically();
         }
@@ -137,6 +346,26 @@ impl TGAImage {
         Ok(())
     }
 
+    /// Unpacks 16-bit ARGB1555 pixels into BGR, or BGRA when the alpha bit is meaningful.
+    fn expand_argb1555(&mut self, alpha: bool) {
+        let bytespp = if alpha { 4 } else { 3 };
+        let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+
+        let mut data = Vec::with_capacity(self.data.len() / 2 * bytespp);
+        for px in self.data.chunks_exact(2) {
+            let v = u16::from_le_bytes([px[0], px[1]]);
+            data.push(expand(v & 0x1f));
+            data.push(expand((v >> 5) & 0x1f));
+            data.push(expand((v >> 10) & 0x1f));
+            if alpha {
+                data.push(if v & 0x8000 != 0 { 255 } else { 0 });
+            }
+        }
+
+        self.data = data;
+        self.bytespp = bytespp;
+    }
+
     #[allow(dead_code)]
     fn load_rle_data(&mut self, file: &mut File) -> io::Result<()> {
         let pixelcount = (self.width * self.height) as usize;
@@ -234,6 +463,73 @@ impl TGAImage {
         Ok(())
     }
 
+    /// Writes an uncompressed 24-bit BMP. Alpha is dropped and grayscale is replicated into all
+    /// three channels. Row 0 of the image ends up at the top, as with `write_tga_file`.
+    #[allow(dead_code)]
+    pub fn write_bmp_file(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+
+        // Rows are padded to a multiple of 4 bytes
+        let row_size = (self.width as usize * 3 + 3) & !3;
+        let pixels_size = row_size * self.height as usize;
+        let offset = 14 + 40;
+
+        // BITMAPFILEHEADER
+        out.write_all(b"BM")?;
+        out.write_all(&((offset + pixels_size) as u32).to_le_bytes())?;
+        out.write_all(&[0; 4])?;
+        out.write_all(&(offset as u32).to_le_bytes())?;
+        // BITMAPINFOHEADER
+        out.write_all(&40u32.to_le_bytes())?;
+        out.write_all(&self.width.to_le_bytes())?;
+        out.write_all(&self.height.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&24u16.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&(pixels_size as u32).to_le_bytes())?;
+        // 72 DPI, no palette
+        out.write_all(&2835i32.to_le_bytes())?;
+        out.write_all(&2835i32.to_le_bytes())?;
+        out.write_all(&[0; 8])?;
+
+        // Positive height means bottom-up rows
+        let mut row = Vec::with_capacity(row_size);
+        let line_bytes = (self.width as usize * self.bytespp).max(1);
+        for line in self.data.chunks_exact(line_bytes).rev() {
+            row.clear();
+            for p in line.chunks_exact(self.bytespp) {
+                match p {
+                    [gray] => row.extend_from_slice(&[*gray; 3]),
+                    _ => row.extend_from_slice(&p[..3]),
+                }
+            }
+            row.resize(row_size, 0);
+            out.write_all(&row)?;
+        }
+
+        out.flush()
+    }
+
+    /// Writes a binary (P6) PPM. Alpha is dropped and grayscale is replicated into all three
+    /// channels. Rows are stored top-down, so row 0 of the image ends up at the top, as with
+    /// `write_tga_file`.
+    #[allow(dead_code)]
+    pub fn write_ppm_file(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+
+        let mut rgb = Vec::with_capacity((self.width * self.height) as usize * 3);
+        for p in self.data.chunks_exact(self.bytespp.max(1)) {
+            match p {
+                [gray] => rgb.extend_from_slice(&[*gray; 3]),
+                _ => rgb.extend_from_slice(&[p[2], p[1], p[0]]),
+            }
+        }
+        out.write_all(&rgb)?;
+
+        out.flush()
+    }
+
     fn unload_rle_data(&self, file: &mut File) -> io::Result<()> {
         const MAX_CHUNK_LENGTH: usize = 128;
         let npixels = (self.width * self.height) as usize;
@@ -301,6 +597,80 @@ impl TGAImage {
         true
     }
 
+    /// Pixel bytes of each scanline from `y = 0` up, `width * bytespp` bytes per row in the
+    /// image's BGR(A) order.
+    #[allow(dead_code)]
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let stride = self.width as usize * self.bytespp;
+        self.data
+            .chunks_exact(stride.max(1))
+            .take(self.height as usize)
+    }
+
+    /// Mutable counterpart of `rows`.
+    #[allow(dead_code)]
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let stride = self.width as usize * self.bytespp;
+        self.data
+            .chunks_exact_mut(stride.max(1))
+            .take(self.height as usize)
+    }
+
+    /// Pixel bytes of scanline `y`, or `None` if it is outside the image.
+    #[allow(dead_code)]
+    pub fn row(&self, y: i32) -> Option<&[u8]> {
+        if y < 0 || y >= self.height {
+            return None;
+        }
+        let stride = self.width as usize * self.bytespp;
+        self.data
+            .get(y as usize * stride..(y as usize + 1) * stride)
+    }
+
+    /// Blends `c` over the existing pixel with the given opacity in [0, 1].
+    #[allow(dead_code)]
+    pub fn blend(&mut self, x: i32, y: i32, c: &TGAColor, alpha: f32) -> bool {
+        let Some(dst) = self.get(x, y) else {
+            return false;
+        };
+        let alpha = alpha.clamp(0.0, 1.0);
+        let mut raw = dst.raw;
+        for (d, s) in raw.iter_mut().zip(c.raw).take(self.bytespp.min(3)) {
+            *d = clamp_u8((*d as f32 * (1.0 - alpha) + s as f32 * alpha).round());
+        }
+        self.set(
+            x,
+            y,
+            &TGAColor {
+                raw,
+                bytespp: self.bytespp,
+            },
+        )
+    }
+
+    /// Draws `text` in a built-in 5x7 font with its first glyph's top-left corner at `(x, y)`.
+    /// Rows run downwards, as in a saved TGA file. Glyphs advance 6 pixels; characters outside
+    /// printable ASCII leave a blank cell. Pixels outside the image are clipped.
+    #[allow(dead_code)]
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, color: &TGAColor) {
+        for (i, c) in text.chars().enumerate() {
+            let Some(glyph) = (c as usize)
+                .checked_sub(32)
+                .and_then(|idx| FONT_5X7.get(idx))
+            else {
+                continue;
+            };
+            let left = x + 6 * i as i32;
+            for (dx, column) in glyph.iter().enumerate() {
+                for dy in 0..7 {
+                    if column & (1 << dy) != 0 {
+                        self.set(left + dx as i32, y + dy, color);
+                    }
+                }
+            }
+        }
+    }
+
     #[allow(dead_code)]
     pub fn flip_horizontally(&mut self) -> bool {
         if self.data.is_empty() {
@@ -340,11 +710,342 @@ impl TGAImage {
         true
     }
 
+    /// Per-channel value counts, indexed as `[red, green, blue][value]`. Alpha is ignored and a
+    /// grayscale image counts its single channel in all three.
+    #[allow(dead_code)]
+    pub fn histogram(&self) -> [[u32; 256]; 3] {
+        let mut hist = [[0u32; 256]; 3];
+        for [r, g, b] in self.pixels_rgb() {
+            hist[0][r as usize] += 1;
+            hist[1][g as usize] += 1;
+            hist[2][b as usize] += 1;
+        }
+        hist
+    }
+
+    /// Smallest and largest Rec. 601 luma over all pixels, or `(0, 0)` for an empty image.
+    #[allow(dead_code)]
+    pub fn min_max_luma(&self) -> (u8, u8) {
+        self.pixels_rgb()
+            .map(|[r, g, b]| {
+                clamp_u8((0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round())
+            })
+            .fold(None, |acc, l| match acc {
+                None => Some((l, l)),
+                Some((lo, hi)) => Some((lo.min(l), hi.max(l))),
+            })
+            .unwrap_or((0, 0))
+    }
+
+    /// Gamma corrects color channels, mapping each normalized value `c` to `c^(1/gamma)`. Alpha
+    /// is kept and a gamma of 1 leaves the image unchanged.
+    pub fn apply_gamma(&mut self, gamma: f32) {
+        if gamma == 1.0 {
+            return;
+        }
+        let channels = self.bytespp.min(3);
+        for pixel in self.data.chunks_exact_mut(self.bytespp) {
+            for c in &mut pixel[..channels] {
+                *c = clamp_u8(((*c as f32 / 255.0).powf(1.0 / gamma) * 255.0).round());
+            }
+        }
+    }
+
+    /// Compresses color channels with the Reinhard operator `c / (1 + c)` on values normalized to
+    /// [0, 1], so saturated pixels end up at half intensity instead of clipping. Alpha is kept.
+    #[allow(dead_code)]
+    pub fn tone_map_reinhard(&mut self) {
+        self.tone_map(1.0);
+    }
+
+    /// Like `tone_map_reinhard`, but first scales by the reciprocal of the average luma so the
+    /// average maps to mid-gray regardless of the overall brightness.
+    #[allow(dead_code)]
+    pub fn tone_map_auto_exposure(&mut self) {
+        let npixels = self.data.len() / self.bytespp;
+        if npixels == 0 {
+            return;
+        }
+        let total: f32 = self
+            .pixels_rgb()
+            .map(|[r, g, b]| (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0)
+            .sum();
+        let average = total / npixels as f32;
+        if average > 0.0 {
+            self.tone_map(1.0 / average);
+        }
+    }
+
+    fn tone_map(&mut self, exposure: f32) {
+        let channels = self.bytespp.min(3);
+        for pixel in self.data.chunks_exact_mut(self.bytespp) {
+            for c in &mut pixel[..channels] {
+                let v = *c as f32 / 255.0 * exposure;
+                *c = clamp_u8((v / (1.0 + v) * 255.0).round());
+            }
+        }
+    }
+
+    /// Averages every pixel with the pixels up to `radius` away in x and y, the square
+    /// `(2 * radius + 1)^2` box around it. Alpha is blurred like the color channels, so
+    /// transparent regions soften along with the colors. Pixels past the border repeat the
+    /// edge. A radius of 0 or less leaves the image unchanged.
+    #[allow(dead_code)]
+    pub fn blur_box(&mut self, radius: i32) {
+        if radius <= 0 {
+            return;
+        }
+        let kernel = vec![1.0 / (2 * radius + 1) as f32; (2 * radius + 1) as usize];
+        self.convolve_separable(&kernel);
+    }
+
+    /// Gaussian blur with standard deviation `sigma` in pixels, truncated at 3 sigma. Channels,
+    /// alpha included, and borders are treated as in `blur_box`. A sigma of 0 or less leaves
+    /// the image unchanged.
+    #[allow(dead_code)]
+    pub fn blur_gaussian(&mut self, sigma: f32) {
+        if sigma <= 0.0 {
+            return;
+        }
+        let radius = (3.0 * sigma).ceil() as i32;
+        let weights: Vec<f32> = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+        let kernel: Vec<f32> = weights.iter().map(|w| w / total).collect();
+        self.convolve_separable(&kernel);
+    }
+
+    /// Convolves every channel with the odd-length `kernel` along x and then along y,
+    /// clamping lookups to the image.
+    fn convolve_separable(&mut self, kernel: &[f32]) {
+        let (w, h, bpp) = (self.width, self.height, self.bytespp);
+        let radius = (kernel.len() / 2) as i32;
+        let mut values: Vec<f32> = self.data.iter().map(|&c| c as f32).collect();
+        for (dx, dy) in [(1, 0), (0, 1)] {
+            let src = values.clone();
+            for y in 0..h {
+                for x in 0..w {
+                    let idx = (x + y * w) as usize * bpp;
+                    for c in 0..bpp {
+                        values[idx + c] = kernel
+                            .iter()
+                            .enumerate()
+                            .map(|(k, weight)| {
+                                let offset = k as i32 - radius;
+                                let sx = (x + offset * dx).clamp(0, w - 1);
+                                let sy = (y + offset * dy).clamp(0, h - 1);
+                                src[(sx + sy * w) as usize * bpp + c] * weight
+                            })
+                            .sum();
+                    }
+                }
+            }
+        }
+        for (dst, v) in self.data.iter_mut().zip(values) {
+            *dst = clamp_u8(v.round());
+        }
+    }
+
+    /// Grayscale image of the luma gradient magnitude from the 3x3 Sobel operator, scaled so a
+    /// black to white step across x or y gives 255. Pixels past the border repeat the edge.
+    #[allow(dead_code)]
+    pub fn sobel(&self) -> TGAImage {
+        let (w, h) = (self.width, self.height);
+        let luma: Vec<f32> = self
+            .pixels_rgb()
+            .map(|[r, g, b]| 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32)
+            .collect();
+        let at = |x: i32, y: i32| luma[(x.clamp(0, w - 1) + y.clamp(0, h - 1) * w) as usize];
+
+        let mut edges = TGAImage::new(w, h, Format::Grayscale);
+        for y in 0..h {
+            for x in 0..w {
+                let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1);
+                let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1);
+                edges.data[(x + y * w) as usize] = clamp_u8((gx.hypot(gy) / 4.0).round());
+            }
+        }
+        edges
+    }
+
+    fn pixels_rgb(&self) -> impl Iterator<Item = [u8; 3]> + '_ {
+        self.data.chunks_exact(self.bytespp).map(|p| match p {
+            [gray] => [*gray; 3],
+            [b, g, r, ..] => [*r, *g, *b],
+            _ => unreachable!("unsupported bytes per pixel: {}", p.len()),
+        })
+    }
+
     #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.data.fill(0);
     }
 
+    /// Morphological antialiasing. Finds high-contrast edges between neighbouring pixels and
+    /// follows each straight horizontal or vertical run of them. Where a run ends in a step (an
+    /// L, Z or U shape), a line is drawn from the middle of the step across the run, and each
+    /// pixel the line cuts is blended with its neighbour across the run by the area it covers.
+    /// Flat regions and runs without steps are left untouched.
+    #[allow(dead_code)]
+    pub fn apply_mlaa(&mut self) {
+        // Luma difference, out of 255, above which neighbouring pixels form an edge
+        const THRESHOLD: f32 = 25.0;
+        let (w, h) = (self.width, self.height);
+        let source = self.clone();
+        let luma: Vec<f32> = self
+            .pixels_rgb()
+            .map(|[r, g, b]| 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32)
+            .collect();
+        let differs = |(x0, y0): (i32, i32), (x1, y1): (i32, i32)| {
+            x1 < w
+                && y1 < h
+                && (luma[(x0 + y0 * w) as usize] - luma[(x1 + y1 * w) as usize]).abs() > THRESHOLD
+        };
+
+        // Horizontal runs lie between rows `c` and `c + 1` and vertical runs, handled by the
+        // transposed pass, between columns; `pixel` maps (along, across) to image coordinates.
+        for transposed in [false, true] {
+            let (along_len, across_len) = if transposed { (h, w) } else { (w, h) };
+            let pixel = |a: i32, c: i32| if transposed { (c, a) } else { (a, c) };
+            // Edge between (a, c) and (a, c + 1), and the step edge between (a, c) and (a + 1, c)
+            let edge = |a: i32, c: i32| differs(pixel(a, c), pixel(a, c + 1));
+            let step =
+                |a: i32, c: i32| a >= 0 && c < across_len && differs(pixel(a, c), pixel(a + 1, c));
+            // +1 for a step on the `c` side of the run, -1 on the `c + 1` side
+            let side = |a: i32, c: i32| match (step(a, c), step(a, c + 1)) {
+                (true, false) => 1.0,
+                (false, true) => -1.0,
+                _ => 0.0,
+            };
+
+            for c in 0..across_len - 1 {
+                let mut a = 0;
+                while a < along_len {
+                    if !edge(a, c) {
+                        a += 1;
+                        continue;
+                    }
+                    let start = a;
+                    while a < along_len && edge(a, c) {
+                        a += 1;
+                    }
+                    let (left, right) = (side(start - 1, c), side(a - 1, c));
+                    let len = (a - start) as f32;
+                    // Height of the line above the run, in pixels towards the `c` side
+                    let height = |t: f32| match (left != 0.0, right != 0.0) {
+                        (true, true) if t < len / 2.0 => left * 0.5 * (1.0 - 2.0 * t / len),
+                        (true, true) => right * 0.5 * (2.0 * t / len - 1.0),
+                        (true, false) => left * 0.5 * (1.0 - t / len),
+                        (false, true) => right * 0.5 * t / len,
+                        (false, false) => 0.0,
+                    };
+
+                    for i in start..a {
+                        // Area the line covers on either side, from a few samples per pixel
+                        let (mut above, mut below) = (0.0, 0.0);
+                        for k in 0..4 {
+                            let hk = height((i - start) as f32 + (k as f32 + 0.5) / 4.0);
+                            above += hk.max(0.0) / 4.0;
+                            below += (-hk).max(0.0) / 4.0;
+                        }
+                        let ((x0, y0), (x1, y1)) = (pixel(i, c), pixel(i, c + 1));
+                        let (near, far) = (source.get(x0, y0), source.get(x1, y1));
+                        if let (Some(near), Some(far)) = (near, far) {
+                            if above > 0.0 {
+                                self.blend(x0, y0, &far, above);
+                            }
+                            if below > 0.0 {
+                                self.blend(x1, y1, &near, below);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Quantizes each color channel to `levels` evenly spaced steps, diffusing the quantization
+    /// error to neighbouring pixels with the Floyd–Steinberg kernel.
+    #[allow(dead_code)]
+    pub fn apply_floyd_steinberg(&mut self, levels: u8) {
+        if levels < 2 || self.data.is_empty() {
+            return;
+        }
+        let step = 255.0 / (levels - 1) as f32;
+        let channels = self.bytespp.min(3);
+        let mut error = vec![0.0f32; self.data.len()];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = ((x + y * self.width) * self.bytespp as i32) as usize;
+                for c in 0..channels {
+                    let old = (self.data[idx + c] as f32 + error[idx + c]).clamp(0.0, 255.0);
+                    let new = ((old / step).round() * step).clamp(0.0, 255.0);
+                    self.data[idx + c] = clamp_u8(new.round());
+
+                    let err = old - new;
+                    // Error that would fall outside the image is dropped
+                    for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || nx >= self.width || ny >= self.height {
+                            continue;
+                        }
+                        let nidx = ((nx + ny * self.width) * self.bytespp as i32) as usize;
+                        error[nidx + c] += err * weight / 16.0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds a mip pyramid starting with a copy of this image, halving each dimension per level
+    /// with a 2x2 box filter until reaching 1x1.
+    #[allow(dead_code)]
+    pub fn generate_mipmaps(&self) -> Vec<TGAImage> {
+        let mut pyramid = vec![self.clone()];
+        loop {
+            let prev = &pyramid[pyramid.len() - 1];
+            if prev.width <= 1 && prev.height <= 1 {
+                break;
+            }
+            let w = (prev.width / 2).max(1);
+            let h = (prev.height / 2).max(1);
+            let mut level = TGAImage {
+                data: vec![0; (w * h) as usize * prev.bytespp],
+                width: w,
+                height: h,
+                bytespp: prev.bytespp,
+            };
+
+            for y in 0..h {
+                for x in 0..w {
+                    let mut sum = [0u32; 4];
+                    for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let px = (x * 2 + sx).min(prev.width - 1);
+                        let py = (y * 2 + sy).min(prev.height - 1);
+                        let raw = prev.get(px, py).unwrap().raw;
+                        for (s, &b) in sum.iter_mut().zip(raw.iter()) {
+                            *s += b as u32;
+                        }
+                    }
+                    let idx = ((x + y * w) as usize) * level.bytespp;
+                    for (d, s) in level.data[idx..idx + level.bytespp].iter_mut().zip(sum) {
+                        *d = ((s + 2) / 4) as u8;
+                    }
+                }
+            }
+            pyramid.push(level);
+        }
+        pyramid
+    }
+
     #[allow(dead_code)]
     pub fn scale(&mut self, w: i32, h: i32) -> bool {
         if w <= 0 || h <= 0 || self.data.is_empty() {
@@ -397,6 +1098,105 @@ impl TGAImage {
 
         true
     }
+
+    /// Changes the canvas size without scaling. Existing pixels keep their coordinates, so the
+    /// image stays anchored at (0, 0); new pixels are set to `fill` and shrinking crops.
+    #[allow(dead_code)]
+    pub fn resize_canvas(&mut self, w: i32, h: i32, fill: &TGAColor) -> bool {
+        if w <= 0 || h <= 0 {
+            return false;
+        }
+        let old = std::mem::replace(
+            self,
+            TGAImage {
+                data: vec![0; (w * h) as usize * self.bytespp],
+                width: w,
+                height: h,
+                bytespp: self.bytespp,
+            },
+        );
+
+        for y in 0..h {
+            for x in 0..w {
+                let c = old.get(x, y).unwrap_or(*fill);
+                self.set(x, y, &c);
+            }
+        }
+
+        true
+    }
+}
+
+/// Floating-point image for values outside the 0-255 range, such as accumulated light.
+/// Channels are stored in RGB(A) order, pixel after pixel and row after row like `TGAImage`.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    pub data: Vec<f32>,
+    pub width: i32,
+    pub height: i32,
+    pub channels: usize,
+}
+
+#[allow(dead_code)]
+impl HdrImage {
+    pub fn new(width: i32, height: i32, channels: usize) -> HdrImage {
+        HdrImage {
+            data: vec![0.0; (width * height) as usize * channels],
+            width,
+            height,
+            channels,
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&[f32]> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = (x + y * self.width) as usize * self.channels;
+        Some(&self.data[idx..idx + self.channels])
+    }
+
+    pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut [f32]> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = (x + y * self.width) as usize * self.channels;
+        Some(&mut self.data[idx..idx + self.channels])
+    }
+
+    /// Converts to an 8-bit image of the matching format with the Reinhard operator
+    /// `c / (1 + c)`, so any value stays below saturation. Alpha is clamped instead.
+    ///
+    /// # Panics
+    ///
+    /// If `channels` is not 1, 3 or 4.
+    pub fn tone_map_reinhard(&self) -> TGAImage {
+        let format = match self.channels {
+            1 => Format::Grayscale,
+            3 => Format::RGB,
+            4 => Format::RGBA,
+            n => panic!("Cannot tone map an image with {} channels", n),
+        };
+        let mut image = TGAImage::new(self.width, self.height, format);
+        let reinhard = |c: f32| clamp_u8((c.max(0.0) / (1.0 + c.max(0.0)) * 255.0).round());
+        for (dst, src) in image
+            .data
+            .chunks_exact_mut(self.channels)
+            .zip(self.data.chunks_exact(self.channels))
+        {
+            match src {
+                [gray] => dst[0] = reinhard(*gray),
+                [r, g, b, rest @ ..] => {
+                    dst[..3].copy_from_slice(&[reinhard(*b), reinhard(*g), reinhard(*r)]);
+                    if let Some(a) = rest.first() {
+                        dst[3] = clamp_u8((a * 255.0).round());
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        image
+    }
 }
 
 #[cfg(test)]
@@ -418,4 +1218,347 @@ mod tests {
 
         assert_eq!(image.data, testimage.data);
     }
+
+    #[test]
+    fn test_rows() {
+        let mut image = TGAImage::new(5, 3, Format::RGBA);
+        image.set(4, 1, &TGAColor::rgba(1, 2, 3, 4));
+        assert_eq!(image.rows().count(), 3);
+        assert!(image.rows().all(|row| row.len() == 5 * 4));
+        assert_eq!(image.row(1).unwrap()[16..], [3, 2, 1, 4]);
+        assert!(image.row(3).is_none() && image.row(-1).is_none());
+
+        for row in image.rows_mut() {
+            row[0] = 9;
+        }
+        assert!((0..3).all(|y| image.get(0, y).unwrap().raw[0] == 9));
+    }
+
+    #[test]
+    fn test_blur() {
+        let dot = || {
+            let mut image = TGAImage::new(9, 9, Format::Grayscale);
+            image.set(4, 4, &TGAColor::from_slice(&[255], 1));
+            image
+        };
+        let at = |image: &TGAImage, x: i32, y: i32| image.get(x, y).unwrap().raw[0];
+
+        let mut image = dot();
+        image.blur_box(1);
+        for y in 0..9 {
+            for x in 0..9 {
+                let inside = (x - 4i32).abs() <= 1 && (y - 4i32).abs() <= 1;
+                assert_eq!(
+                    at(&image, x, y),
+                    if inside { 28 } else { 0 },
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+
+        let mut image = dot();
+        image.blur_gaussian(1.0);
+        let center = at(&image, 4, 4);
+        assert!(center > 0 && center < 255);
+        for d in 1..=3 {
+            let ring =
+                [(4 + d, 4), (4 - d, 4), (4, 4 + d), (4, 4 - d)].map(|(x, y)| at(&image, x, y));
+            assert!(ring.iter().all(|&v| v == ring[0]), "ring {}: {:?}", d, ring);
+            assert!(ring[0] < at(&image, 4 + d - 1, 4));
+            assert_eq!(at(&image, 4 + d, 4 + d), at(&image, 4 - d, 4 - d));
+        }
+    }
+
+    #[test]
+    fn test_sobel() {
+        // Black left half, white right half
+        let mut image = TGAImage::new(8, 4, Format::RGB);
+        for y in 0..4 {
+            for x in 4..8 {
+                image.set(x, y, &TGAColor::rgba(255, 255, 255, 255));
+            }
+        }
+        let edges = image.sobel();
+        assert_eq!(edges.format(), Some(Format::Grayscale));
+        for y in 0..4 {
+            for x in 0..8 {
+                let expected = if x == 3 || x == 4 { 255 } else { 0 };
+                assert_eq!(edges.get(x, y).unwrap().raw[0], expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize_canvas() {
+        let red = TGAColor::rgba(255, 0, 0, 255);
+        let mut image = TGAImage::new(2, 2, Format::RGB);
+        for (i, (x, y)) in [(0, 0), (1, 0), (0, 1), (1, 1)].into_iter().enumerate() {
+            image.set(x, y, &TGAColor::rgba(0, 0, 64 * i as u8, 255));
+        }
+        let original = image.clone();
+
+        assert!(image.resize_canvas(4, 4, &red));
+        assert_eq!((image.width, image.height), (4, 4));
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = original.get(x, y).unwrap_or(red);
+                assert_eq!(image.get(x, y).unwrap().raw[..3], expected.raw[..3]);
+            }
+        }
+
+        assert!(image.resize_canvas(1, 1, &red));
+        assert_eq!(image.data, original.get(0, 0).unwrap().raw[..3]);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut image = TGAImage::new(2, 2, Format::RGB);
+        image.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        image.set(1, 0, &TGAColor::rgba(255, 255, 255, 255));
+        image.set(0, 1, &TGAColor::rgba(0, 0, 255, 255));
+
+        let [r, g, b] = image.histogram();
+        assert_eq!((r[255], r[0]), (2, 2));
+        assert_eq!((g[255], g[0]), (1, 3));
+        assert_eq!((b[255], b[0]), (2, 2));
+        assert_eq!(r.iter().sum::<u32>(), 4);
+
+        assert_eq!(image.min_max_luma(), (0, 255));
+        image.set(1, 1, &TGAColor::rgba(0, 0, 255, 255));
+        assert_eq!(image.min_max_luma(), (29, 255));
+    }
+
+    #[test]
+    fn test_tone_map() {
+        let mut image = TGAImage::new(2, 1, Format::RGB);
+        image.set(0, 0, &TGAColor::rgba(255, 255, 255, 255));
+        image.set(1, 0, &TGAColor::rgba(128, 128, 128, 255));
+        image.tone_map_reinhard();
+        assert_eq!(image.get(0, 0).unwrap().raw[..3], [128, 128, 128]);
+        assert_eq!(image.get(1, 0).unwrap().raw[..3], [85, 85, 85]);
+
+        // A uniformly mid-gray image is its own average, so it stays mid-gray.
+        let mut image = TGAImage::new(2, 2, Format::RGB);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            image.set(x, y, &TGAColor::rgba(128, 128, 128, 255));
+        }
+        image.tone_map_auto_exposure();
+        for [r, g, b] in image.pixels_rgb() {
+            assert!([r, g, b].iter().all(|&c| (c as i32 - 128).abs() <= 1));
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            TGAImage::new(1, 1, Format::Grayscale).format(),
+            Some(Format::Grayscale)
+        );
+        assert_eq!(
+            TGAImage::new(1, 1, Format::RGBA).format(),
+            Some(Format::RGBA)
+        );
+
+        let mut image = TGAImage::new(0, 0, Format::RGB);
+        image
+            .read_tga_file_checked("tests/images/dot.tga")
+            .expect("Failed to read image.");
+        assert_eq!(image.format(), Some(Format::RGB));
+        assert_eq!((image.width, image.height), (2, 2));
+
+        let mut image = TGAImage::new(0, 0, Format::Grayscale);
+        let err = image
+            .read_tga_file_checked("tests/images/dot.tga")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(image.format(), Some(Format::Grayscale));
+    }
+
+    #[test]
+    fn test_write_bmp_file() {
+        let mut image = TGAImage::new(3, 2, Format::RGB);
+        image.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        image.set(2, 1, &TGAColor::rgba(0, 0, 255, 255));
+
+        let path = std::env::temp_dir().join("tinyrenderer_test_write_bmp_file.bmp");
+        let path = path.to_str().unwrap();
+        image.write_bmp_file(path).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        assert_eq!(&bytes[..2], b"BM");
+        assert_eq!(u32_at(2) as usize, bytes.len());
+        assert_eq!(u32_at(10), 54);
+        assert_eq!((u32_at(18), u32_at(22)), (3, 2));
+        assert_eq!(u16::from_le_bytes([bytes[28], bytes[29]]), 24);
+
+        // 9 bytes of pixels padded to 12, bottom row (y = 1) first
+        let rows = &bytes[54..];
+        assert_eq!(rows.len(), 2 * 12);
+        assert_eq!(rows[6..9], [255, 0, 0]);
+        assert_eq!(rows[9..12], [0, 0, 0]);
+        assert_eq!(rows[12..15], [0, 0, 255]);
+    }
+
+    #[test]
+    fn test_write_ppm_file() {
+        let mut image = TGAImage::new(3, 2, Format::RGBA);
+        image.set(0, 0, &TGAColor::rgba(255, 0, 0, 255));
+        image.set(1, 0, &TGAColor::rgba(10, 20, 30, 0));
+        image.set(2, 1, &TGAColor::rgba(0, 0, 255, 255));
+
+        let path = std::env::temp_dir().join("tinyrenderer_test_write_ppm_file.ppm");
+        let path = path.to_str().unwrap();
+        image.write_ppm_file(path).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        // RGB triples, top row (y = 0) first, alpha dropped
+        let pixels = &bytes[header.len()..];
+        assert_eq!(pixels.len(), 3 * 2 * 3);
+        assert_eq!(pixels[..9], [255, 0, 0, 10, 20, 30, 0, 0, 0]);
+        assert_eq!(pixels[15..18], [0, 0, 255]);
+
+        let mut gray = TGAImage::new(1, 1, Format::Grayscale);
+        gray.set(0, 0, &TGAColor::from_slice(&[7], 1));
+        gray.write_ppm_file(path).unwrap();
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(bytes, b"P6\n1 1\n255\n\x07\x07\x07");
+    }
+
+    #[test]
+    fn test_floyd_steinberg() {
+        let (w, h) = (64, 8);
+        let mut image = TGAImage::new(w, h, Format::Grayscale);
+        for y in 0..h {
+            for x in 0..w {
+                let v = (x * 255 / (w - 1)) as u8;
+                image.set(x, y, &TGAColor::from_slice(&[v], 1));
+            }
+        }
+        image.apply_floyd_steinberg(2);
+
+        assert!(image.data.iter().all(|&v| v == 0 || v == 255));
+        assert!(image.data.contains(&0));
+        assert!(image.data.contains(&255));
+
+        // A hard threshold would give every row a single black-to-white transition.
+        let transitions = (0..w - 1)
+            .filter(|&x| image.get(x, 0).unwrap().raw[0] != image.get(x + 1, 0).unwrap().raw[0])
+            .count();
+        assert!(transitions > 1, "transitions: {}", transitions);
+    }
+
+    #[test]
+    fn test_generate_mipmaps() {
+        let mut image = TGAImage::new(2, 2, Format::RGB);
+        image.set(0, 0, &TGAColor::rgba(0, 10, 100, 255));
+        image.set(1, 0, &TGAColor::rgba(40, 20, 100, 255));
+        image.set(0, 1, &TGAColor::rgba(80, 30, 200, 255));
+        image.set(1, 1, &TGAColor::rgba(120, 40, 200, 255));
+
+        let pyramid = image.generate_mipmaps();
+        assert_eq!(pyramid.len(), 2);
+        assert_eq!(pyramid[0].data, image.data);
+        assert_eq!((pyramid[1].width, pyramid[1].height), (1, 1));
+        assert_eq!(pyramid[1].data, vec![150, 25, 60]);
+    }
+
+    #[test]
+    fn test_hsv() {
+        assert_eq!(TGAColor::from_hsv(0.0, 1.0, 1.0).raw, [0, 0, 255, 255]);
+        assert_eq!(TGAColor::from_hsv(120.0, 1.0, 1.0).raw, [0, 255, 0, 255]);
+
+        let (h, s, v) = TGAColor::rgba(255, 128, 0, 255).to_hsv();
+        assert!((h - 30.0).abs() < 1.0, "h: {}", h);
+        assert!((s - 1.0).abs() < 1e-6 && (v - 1.0).abs() < 1e-6);
+        let [b, g, r, _] = TGAColor::from_hsv(h, s, v).raw;
+        assert_eq!((r, g, b), (255, 128, 0));
+    }
+
+    #[test]
+    fn test_read_16bit_tga() {
+        let mut image = TGAImage::new(1, 1, Format::RGB);
+        image.read_tga_file("tests/images/rgb16.tga").unwrap();
+
+        assert_eq!(image.bytespp, 3);
+        assert_eq!(image.get(0, 0).unwrap().raw, [0, 0, 255, 0]);
+        // r = 0, g = 16, b = 8 in 5-bit channels
+        assert_eq!(image.get(1, 0).unwrap().raw, [66, 132, 0, 0]);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        // 'A' is columns 0x7E 0x11 0x11 0x11 0x7E: two legs and a crossbar on row 4.
+        let mut image = TGAImage::new(8, 8, Format::RGB);
+        let white = TGAColor::rgba(255, 255, 255, 255);
+        image.draw_text(0, 0, "A", &white);
+        let lit = |x, y| image.get(x, y).unwrap().raw[0] == 255;
+        assert!(!lit(0, 0));
+        assert!(lit(0, 1) && lit(0, 6) && lit(4, 6));
+        assert!(lit(2, 0) && lit(2, 4));
+        assert!(!lit(2, 2) && !lit(2, 6));
+        assert!(!lit(5, 3));
+
+        // Glyphs running past the edge are clipped instead of wrapping
+        let mut image = TGAImage::new(8, 8, Format::RGB);
+        image.draw_text(4, 4, "AA", &white);
+        assert_eq!(image.get(0, 5).unwrap().raw[0], 0);
+    }
+
+    #[test]
+    fn test_mlaa() {
+        // White above the diagonal, black below it
+        let mut image = TGAImage::new(16, 16, Format::RGB);
+        for y in 0..16 {
+            for x in (y + 1)..16 {
+                image.set(x, y, &TGAColor::rgba(255, 255, 255, 255));
+            }
+        }
+        let original = image.clone();
+        image.apply_mlaa();
+
+        let blended = (0..15)
+            .filter(|&y| {
+                let c = image.get(y + 1, y).unwrap().raw[0];
+                c > 0 && c < 255
+            })
+            .count();
+        assert!(blended > 10, "only {} stair steps blended", blended);
+        for (x, y) in [(0, 15), (3, 10), (15, 0), (10, 3)] {
+            assert_eq!(
+                image.get(x, y).unwrap().raw,
+                original.get(x, y).unwrap().raw
+            );
+        }
+
+        let mut flat = TGAImage::new(8, 8, Format::RGB);
+        for y in 0..8 {
+            for x in 0..8 {
+                flat.set(x, y, &TGAColor::rgba(90, 120, 30, 255));
+            }
+        }
+        let before = flat.clone();
+        flat.apply_mlaa();
+        assert_eq!(flat.data, before.data);
+    }
+
+    #[test]
+    fn test_hdr_tone_map() {
+        let mut hdr = HdrImage::new(2, 1, 3);
+        hdr.get_mut(0, 0).unwrap().copy_from_slice(&[1.0, 3.0, 0.0]);
+        hdr.get_mut(1, 0)
+            .unwrap()
+            .copy_from_slice(&[0.25, -1.0, 9.0]);
+        let image = hdr.tone_map_reinhard();
+        assert_eq!(image.format(), Some(Format::RGB));
+        assert_eq!(image.get(0, 0).unwrap().raw[..3], [0, 191, 128]);
+        assert_eq!(image.get(1, 0).unwrap().raw[..3], [230, 0, 51]);
+    }
 }